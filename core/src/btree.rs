@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::dsl::*;
+use crate::error::*;
+use crate::relational::*;
+use crate::types::*;
+
+//The operations that keep a subset of the pairs return a BTree, the others a Table
+impl Relation for BTree {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    fn shape(&self) -> Shape {
+        Shape::KV(self.data.len())
+    }
+
+    fn rows(&self) -> RowsIter<Self>
+    where
+        Self: Sized,
+    {
+        RowsIter::new(self.clone())
+    }
+
+    fn as_seq(&self) -> Seq {
+        Seq::of_rows(self.schema.clone(), &self.shape(), self.clone())
+    }
+
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(2)?;
+        let data = self
+            .as_rows()
+            .into_iter()
+            .filter(|x| pred.eval(x))
+            .collect();
+        Ok(Self::from_rows(self.schema.clone(), data)?.into())
+    }
+
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        self.to_table().union(other)
+    }
+
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        let data = diff_rows(&self.as_rows(), aligned_rows(&self.schema, other)?);
+        Ok(Self::from_rows(self.schema.clone(), data)?.into())
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        let data = intersect_rows(&self.as_rows(), aligned_rows(&self.schema, other)?);
+        Ok(Self::from_rows(self.schema.clone(), data)?.into())
+    }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        self.to_table().sort(keys)
+    }
+
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        let data = limit_rows(&self.as_rows(), skip, take);
+        Ok(Self::from_rows(self.schema.clone(), data)?.into())
+    }
+
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        let pos = distinct_positions(&self.schema, on)?;
+        let data = distinct_rows(&self.as_rows(), |x| project_row(&pos, x));
+
+        Ok(Self::from_rows(self.schema.clone(), data)?.into())
+    }
+
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        self.to_table().project(cols)
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        let schema = rename_schema(&self.schema, change)?;
+        Ok(Self::new(schema, self.data.clone()).into())
+    }
+}
+
+impl BTree {
+    pub fn new(schema: Schema, data: BTreeMap<Scalar, Scalar>) -> Self {
+        BTree { schema, data }
+    }
+
+    pub fn empty(types: [DataType; 2]) -> Self {
+        Self::new(schema_kv(types), BTreeMap::new())
+    }
+
+    /// From the pairs, a repeated key keep the last value
+    pub fn from_pairs(types: [DataType; 2], pairs: &[(Scalar, Scalar)]) -> Self {
+        Self::new(schema_kv(types), pairs.iter().cloned().collect())
+    }
+
+    /// From rows of [key, value], a row of other width is ArityMismatch
+    pub fn from_rows(schema: Schema, rows: Vec<Col>) -> ResultT<Self> {
+        let mut data = BTreeMap::new();
+        for (pos, row) in rows.into_iter().enumerate() {
+            match <[Scalar; 2]>::try_from(row) {
+                Ok([key, value]) => data.insert(key, value),
+                Err(row) => return Err(Error::ArityMismatch(pos, 2, row.len())),
+            };
+        }
+        Ok(Self::new(schema, data))
+    }
+
+    pub fn to_table(&self) -> Table {
+        Table::new(self.schema.clone(), self.as_rows())
+    }
+
+    fn as_rows(&self) -> Vec<Col> {
+        self.data
+            .iter()
+            .map(|(k, v)| vec![k.clone(), v.clone()])
+            .collect()
+    }
+}
+
+//A map can't be read by position, so the pairs are copied in the snapshot
+//on the first advance
+impl RelIter for RowsIter<BTree> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn advance(&mut self) -> bool {
+        if self.pos == 0 {
+            self.snapshot = self.rel.as_rows();
+        }
+        let ok = self.pos < self.snapshot.len();
+        self.pos += 1;
+        ok
+    }
+
+    fn row(&mut self) -> Col {
+        self.snapshot[self.pos - 1].clone()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.rel.data.len().saturating_sub(self.pos);
+        (left, Some(left))
+    }
+}
@@ -5,12 +5,18 @@ use self::bit_vec::BitVec;
 use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::rc::Rc;
 
+use bytes::Bytes;
+use decorum::R64;
+use rust_decimal::Decimal;
+
+use super::error::*;
 use super::types::*;
 
-pub fn decode<T: From<Scalar>>(values: &[Scalar]) -> Vec<T> {
-    values.iter().map(move |x| From::from(x.clone())).collect()
+pub fn decode<T: TryFrom<Scalar, Error = Error>>(values: &[Scalar]) -> ResultT<Vec<T>> {
+    values.iter().map(move |x| T::try_from(x.clone())).collect()
 }
 
 pub fn to_columns(of: Col) -> Vec<Col> {
@@ -28,7 +34,7 @@ pub fn field(name: &str, kind: DataType) -> Field {
 pub fn schema(names: &[(&str, DataType)]) -> Schema {
     let fields = names
         .iter()
-        .map(|(name, kind)| Field::new(name, *kind))
+        .map(|(name, kind)| Field::new(name, kind.clone()))
         .collect();
 
     Schema::new(fields)
@@ -44,15 +50,15 @@ pub fn schema_it(kind: DataType) -> Schema {
 pub fn schema_build(names: &[(&str, DataType)]) -> Schema {
     let fields = names
         .iter()
-        .map(|(name, kind)| Field::new(name, *kind))
+        .map(|(name, kind)| Field::new(name, kind.clone()))
         .collect();
 
     Schema::new(fields)
 }
 
 pub fn schema_kv(types: [DataType; 2]) -> Schema {
-    let key = field("key", types[0]);
-    let value = field("value", types[1]);
+    let key = field("key", types[0].clone());
+    let value = field("value", types[1].clone());
 
     Schema::new(vec![key, value])
 }
@@ -78,7 +84,6 @@ pub fn infer_types(of: &[Scalar]) -> Vec<DataType> {
 
 pub fn value<T>(x: T) -> Scalar
 where
-    T: From<Scalar>,
     Scalar: From<T>,
 {
     Scalar::from(x)
@@ -86,7 +91,6 @@ where
 
 pub fn rvalue<T>(x: T) -> Rc<Scalar>
 where
-    T: From<Scalar>,
     Scalar: From<T>,
 {
     Rc::new(Scalar::from(x))
@@ -100,6 +104,30 @@ pub fn int64(x: i64) -> Scalar {
     value::<i64>(x)
 }
 
+pub fn float(x: f64) -> Scalar {
+    value::<R64>(R64::from_inner(x))
+}
+
+pub fn dec(num: i64, scale: u32) -> Scalar {
+    value::<Decimal>(Decimal::new(num, scale))
+}
+
+pub fn bits(of: &[bool]) -> Scalar {
+    value::<BitVec>(of.iter().cloned().collect())
+}
+
+pub fn blob(of: &[u8]) -> Scalar {
+    value::<Bytes>(Bytes::from(of))
+}
+
+pub fn sum(tag: &str, of: Scalar) -> Scalar {
+    value::<Variant>(Variant::new(tag, of))
+}
+
+pub fn product(of: &[Scalar]) -> Scalar {
+    Scalar::Product(Rc::new(of.to_vec()))
+}
+
 pub fn bool(x: bool) -> Scalar {
     value::<bool>(x)
 }
@@ -110,7 +138,6 @@ pub fn str(x: &str) -> Scalar {
 
 pub fn col<T>(x: &[T]) -> Vec<Scalar>
 where
-    T: From<Scalar>,
     Scalar: From<T>,
     T: Clone,
 {
@@ -119,7 +146,6 @@ where
 
 pub fn rcol_t<T>(name: &str, kind: DataType, of: &[T]) -> Vector
 where
-    T: From<Scalar>,
     Scalar: From<T>,
     T: Clone,
 {
@@ -130,7 +156,6 @@ where
 
 pub fn rcol<T>(name: &str, of: &[T]) -> Vector
 where
-    T: From<Scalar>,
     Scalar: From<T>,
     T: Clone,
 {
@@ -142,7 +167,6 @@ where
 
 pub fn array<T>(of: &[T]) -> Vector
 where
-    T: From<Scalar>,
     Scalar: From<T>,
     T: Clone,
 {
@@ -166,3 +190,11 @@ pub fn none() -> Scalar {
 pub fn vector(of: &[Scalar]) -> Vector {
     Vector::new_scalars(of)
 }
+
+pub fn row(of: &[Scalar]) -> Row {
+    Row::new_scalars(of)
+}
+
+pub fn kv(types: [DataType; 2], pairs: &[(Scalar, Scalar)]) -> BTree {
+    BTree::from_pairs(types, pairs)
+}
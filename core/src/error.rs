@@ -0,0 +1,74 @@
+use std::fmt;
+
+use crate::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    //Operation between incompatible types
+    TypeMismatch(DataType, DataType),
+    //Convert a scalar (from * into)
+    InvalidCast(DataType, DataType),
+    FieldNotFound(String),
+    //Two columns with the same name
+    DuplicateField(String),
+    ColumnOutOfRange(usize, usize), // pos * columns
+    SchemaMismatch(Schema, Schema),
+    NullNotAllowed(String, usize),      // field * row
+    ArityMismatch(usize, usize, usize), // row * expected * found
+    //The first bad value of a row: row * field * cause
+    InvalidValue(usize, String, Box<Error>),
+    //Arithmetic
+    Overflow(DataType),
+    DivideByZero,
+    //Operands outside of what the operation accept, like sqrt(-1)
+    Domain(String),
+    Unsupported(String, DataType),
+    //A single pass Seq read again
+    StreamConsumed,
+    //Reading a file or stream, with the message of the std error
+    Io(String),
+}
+
+pub type ResultT<T> = Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TypeMismatch(a, b) => write!(f, "Type mismatch: {} <> {}", a, b),
+            Error::InvalidCast(from, to) => write!(f, "Can't convert {} into {}", from, to),
+            Error::FieldNotFound(x) => write!(f, "Field {} not found", x),
+            Error::DuplicateField(x) => write!(f, "Field {} is repeated", x),
+            Error::ColumnOutOfRange(pos, len) => {
+                write!(f, "Column {} out of range, the relation has {}", pos, len)
+            }
+            Error::SchemaMismatch(a, b) => write!(f, "Incompatible schemas: ({}) <> ({})", a, b),
+            Error::NullNotAllowed(name, row) => {
+                write!(f, "Null in the not nullable field {} at row {}", name, row)
+            }
+            Error::ArityMismatch(row, expected, found) => write!(
+                f,
+                "The row {} has {} columns, expected {}",
+                row, found, expected
+            ),
+            Error::InvalidValue(row, name, cause) => {
+                write!(
+                    f,
+                    "Invalid value in the field {} at row {}: {}",
+                    name, row, cause
+                )
+            }
+            Error::Overflow(x) => write!(f, "Overflow in a operation of {}", x),
+            Error::DivideByZero => write!(f, "Division by zero"),
+            Error::Domain(x) => write!(f, "Out of domain: {}", x),
+            Error::Unsupported(op, x) => {
+                write!(f, "The operation {} is not supported for {}", op, x)
+            }
+            Error::StreamConsumed => {
+                write!(f, "The stream was already read, and can be read only once")
+            }
+            Error::Io(x) => write!(f, "I/O error: {}", x),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
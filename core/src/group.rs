@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::*;
+use crate::relational::*;
+use crate::stdlib::math::*;
+use crate::types::*;
+
+/// The running value of a aggregate for one group
+#[derive(Debug, Clone)]
+enum Acc {
+    Count(i64),
+    Sum(Scalar),
+    Min(Scalar),
+    Max(Scalar),
+    Avg(Scalar, i64),
+    First(Option<Scalar>),
+    Last(Scalar),
+    Distinct(HashSet<Scalar>),
+    //A sum or avg that overflow with OnError::Null, and stay null
+    Overflow,
+}
+
+impl Acc {
+    fn new(op: Aggregate) -> Self {
+        match op {
+            Aggregate::Count => Acc::Count(0),
+            Aggregate::Sum => Acc::Sum(Scalar::None),
+            Aggregate::Min => Acc::Min(Scalar::None),
+            Aggregate::Max => Acc::Max(Scalar::None),
+            Aggregate::Avg => Acc::Avg(Scalar::None, 0),
+            Aggregate::First => Acc::First(None),
+            Aggregate::Last => Acc::Last(Scalar::None),
+            Aggregate::CountDistinct => Acc::Distinct(HashSet::new()),
+        }
+    }
+
+    //Like in SQL, only first & last see the nulls
+    fn step(&mut self, value: &Scalar, mode: OnError) -> ResultT<()> {
+        let is_null = *value == Scalar::None;
+        match self {
+            Acc::First(x) => {
+                if x.is_none() {
+                    *x = Some(value.clone())
+                }
+            }
+            Acc::Last(x) => *x = value.clone(),
+            _ if is_null => {}
+            Acc::Overflow => {}
+            Acc::Count(x) => *x += 1,
+            Acc::Sum(x) => match add(x, value, mode)? {
+                Scalar::None => *self = Acc::Overflow,
+                total => *x = total,
+            },
+            Acc::Min(x) => {
+                if *x == Scalar::None || value < x {
+                    *x = value.clone()
+                }
+            }
+            Acc::Max(x) => {
+                if value > x {
+                    *x = value.clone()
+                }
+            }
+            Acc::Avg(x, count) => match add(x, value, mode)? {
+                Scalar::None => *self = Acc::Overflow,
+                total => {
+                    *x = total;
+                    *count += 1;
+                }
+            },
+            Acc::Distinct(x) => {
+                x.insert(value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, mode: OnError) -> ResultT<Scalar> {
+        Ok(match self {
+            Acc::Count(x) => x.into(),
+            Acc::Sum(x) | Acc::Min(x) | Acc::Max(x) | Acc::Last(x) => x,
+            Acc::First(x) => x.unwrap_or_default(),
+            Acc::Avg(Scalar::None, _) | Acc::Overflow => Scalar::None,
+            //A float stay a float, the others are exact as Decimal
+            Acc::Avg(x @ Scalar::F64(_), count) => {
+                math_div_with(&x, &cast_num(&count.into(), DataType::F64)?, mode)?
+            }
+            Acc::Avg(x, count) => {
+                math_div_with(&cast_num(&x, DataType::Decimal)?, &count.into(), mode)?
+            }
+            Acc::Distinct(x) => (x.len() as i64).into(),
+        })
+    }
+}
+
+fn add(total: &Scalar, value: &Scalar, mode: OnError) -> ResultT<Scalar> {
+    match total {
+        Scalar::None => Ok(value.clone()),
+        x => math_add_with(x, value, mode),
+    }
+}
+
+fn field(schema: &Schema, agg: &Agg, pos: usize) -> Field {
+    let source = &schema[pos];
+    let kind = match agg.op {
+        Aggregate::Count | Aggregate::CountDistinct => DataType::I64,
+        Aggregate::Avg if source.kind == DataType::F64 => DataType::F64,
+        Aggregate::Avg => DataType::Decimal,
+        _ => source.kind.clone(),
+    };
+    let name = match &agg.name {
+        Some(x) => x.clone(),
+        None => format!("{:?}_{}", agg.op, source.name).to_lowercase(),
+    };
+    match agg.op {
+        Aggregate::Count | Aggregate::CountDistinct => Field::new_owned(name, kind).not_null(),
+        _ => Field::new_owned(name, kind),
+    }
+}
+
+/// Stream the rows into a hash table by the group key. The groups are returned
+/// in the order they were first seen. Without keys there is always one row.
+pub fn group<T: Relation + ?Sized>(of: &T, by: &[ColumnName], aggs: &[Agg]) -> ResultT<Table> {
+    let schema = of.schema();
+    let keys = schema.resolve_pos_many(by)?;
+    let cols = aggs
+        .iter()
+        .map(|x| schema.resolve_pos(&x.col))
+        .collect::<ResultT<Pos>>()?;
+
+    let mut fields = schema.only(&keys).columns;
+    for (agg, pos) in aggs.iter().zip(cols.iter()) {
+        fields.push(field(&schema, agg, *pos));
+    }
+
+    let mut order: Vec<Col> = Vec::new();
+    let mut groups: HashMap<Col, Vec<Acc>> = HashMap::new();
+
+    let mut iter = of.as_seq().iter()?;
+    while let Some(row) = iter.next() {
+        let key = project_row(&keys, &row);
+        let acc = groups.entry(key.clone()).or_insert_with(|| {
+            order.push(key);
+            aggs.iter().map(|x| Acc::new(x.op)).collect()
+        });
+        for ((acc, pos), agg) in acc.iter_mut().zip(cols.iter()).zip(aggs) {
+            acc.step(&row[*pos], agg.on_error)?;
+        }
+    }
+    if let Some(e) = iter.error() {
+        return Err(e);
+    }
+    //Without keys is a single group, even if there are no rows, like in SQL
+    if keys.is_empty() && order.is_empty() {
+        order.push(vec![]);
+        groups.insert(vec![], aggs.iter().map(|x| Acc::new(x.op)).collect());
+    }
+
+    let data = order
+        .into_iter()
+        .map(|key| {
+            let acc = groups.remove(&key).unwrap();
+            let mut row = key;
+            for (x, agg) in acc.into_iter().zip(aggs) {
+                row.push(x.finish(agg.on_error)?);
+            }
+            Ok(row)
+        })
+        .collect::<ResultT<_>>()?;
+
+    Ok(Table::new(Schema::new(fields), data))
+}
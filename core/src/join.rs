@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use bit_vec::BitVec;
+
+use crate::error::*;
+use crate::relational::*;
+use crate::stdlib::math::{cast_num, promote_kind};
+use crate::types::*;
+
+/// One side of a join, with all the rows pulled
+pub struct Side {
+    pub schema: Schema,
+    pub rows: Vec<Col>,
+}
+
+impl Side {
+    pub fn new<T: Relation + ?Sized>(of: &T) -> ResultT<Self> {
+        Ok(Side {
+            schema: of.schema(),
+            rows: to_rows(of)?,
+        })
+    }
+
+    fn nulls(&self) -> Col {
+        Scalar::repeat(&Scalar::None, self.schema.len())
+    }
+}
+
+fn has_null(row: &[Scalar], pos: &[usize]) -> bool {
+    pos.iter().any(|x| row[*x] == Scalar::None)
+}
+
+fn combine(lhs: &[Scalar], rhs: &[Scalar]) -> Col {
+    lhs.iter().chain(rhs.iter()).cloned().collect()
+}
+
+/// The type each pair of keys is compared as: None if are of the same type,
+/// the larger if both are numbers. Other types never match, so are a error
+fn key_kinds(
+    lhs: &Schema,
+    rhs: &Schema,
+    left: &[usize],
+    right: &[usize],
+) -> ResultT<Vec<Option<DataType>>> {
+    left.iter()
+        .zip(right)
+        .map(|(l, r)| {
+            let (a, b) = (lhs[*l].kind.clone(), rhs[*r].kind.clone());
+            if a == b || a == DataType::Any || b == DataType::Any {
+                Ok(None)
+            } else {
+                promote_kind(a, b).map(Some)
+            }
+        })
+        .collect()
+}
+
+fn project_keys(pos: &[usize], kinds: &[Option<DataType>], row: &[Scalar]) -> ResultT<Col> {
+    pos.iter()
+        .zip(kinds)
+        .map(|(x, kind)| match kind {
+            Some(kind) => cast_num(&row[*x], kind.clone()),
+            None => Ok(row[*x].clone()),
+        })
+        .collect()
+}
+
+/// For each row of the left, the positions of the matching rows of the right.
+/// Equality joins use a hash table on the right side, any other comparison
+/// fallback to a nested loop. Like in SQL, a null key never match.
+fn matches(lhs: &Side, rhs: &Side, on: &[JoinCmp]) -> ResultT<Vec<Vec<usize>>> {
+    let ops: Vec<_> = on.iter().map(|x| x.op).collect();
+    let left = on
+        .iter()
+        .map(|x| lhs.schema.resolve_pos(&x.lhs))
+        .collect::<ResultT<Pos>>()?;
+    let right = on
+        .iter()
+        .map(|x| rhs.schema.resolve_pos(&x.rhs))
+        .collect::<ResultT<Pos>>()?;
+    let kinds = key_kinds(&lhs.schema, &rhs.schema, &left, &right)?;
+
+    if ops.iter().all(|x| *x == CompareOp::Eq) {
+        let mut index: HashMap<Col, Vec<usize>> = HashMap::new();
+        for (i, row) in rhs.rows.iter().enumerate() {
+            if !has_null(row, &right) {
+                let key = project_keys(&right, &kinds, row)?;
+                index.entry(key).or_default().push(i);
+            }
+        }
+
+        lhs.rows
+            .iter()
+            .map(|row| {
+                if has_null(row, &left) {
+                    return Ok(vec![]);
+                }
+                let key = project_keys(&left, &kinds, row)?;
+                Ok(index.get(&key).cloned().unwrap_or_default())
+            })
+            .collect()
+    } else {
+        Ok(lhs
+            .rows
+            .iter()
+            .map(|row| {
+                if has_null(row, &left) {
+                    return vec![];
+                }
+                rhs.rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| {
+                        !has_null(other, &right)
+                            && ops
+                                .iter()
+                                .enumerate()
+                                .all(|(i, op)| op.eval(&row[left[i]], &other[right[i]]))
+                    })
+                    .map(|(i, _)| i)
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+/// Match on the columns with the same name, keeping only one copy of them
+fn natural(lhs: &Side, rhs: &Side) -> ResultT<Table> {
+    let on: Vec<_> = lhs
+        .schema
+        .columns
+        .iter()
+        .filter(|x| rhs.schema.exist(&x.name))
+        .map(|x| {
+            let name = ColumnName::Name(x.name.clone());
+            JoinCmp::eq(name.clone(), name)
+        })
+        .collect();
+
+    let table = join(lhs, rhs, Join::Inner, &on)?;
+    let extra = lhs.schema.join(&rhs.schema);
+    let keep: Vec<_> = (0..lhs.schema.len())
+        .chain(extra.into_iter().map(|x| x + lhs.schema.len()))
+        .map(ColumnName::Pos)
+        .collect();
+
+    match table.project(&keep)? {
+        Rel::Table(x) => Ok(x),
+        _ => unreachable!(),
+    }
+}
+
+pub fn join(lhs: &Side, rhs: &Side, kind: Join, on: &[JoinCmp]) -> ResultT<Table> {
+    match kind {
+        Join::Cross => return join(lhs, rhs, Join::Inner, &[]),
+        Join::Natural => return natural(lhs, rhs),
+        _ => {}
+    }
+
+    let found = matches(lhs, rhs, on)?;
+
+    if let Join::Semi | Join::Anti = kind {
+        let keep = kind == Join::Semi;
+        let data = lhs
+            .rows
+            .iter()
+            .zip(found.iter())
+            .filter(|(_, found)| found.is_empty() != keep)
+            .map(|(row, _)| row.clone());
+        return Ok(Table::new(lhs.schema.clone(), data.collect()));
+    }
+
+    let mut matched = BitVec::from_elem(rhs.rows.len(), false);
+    let mut data = Vec::new();
+
+    for (row, found) in lhs.rows.iter().zip(found.iter()) {
+        for pos in found {
+            matched.set(*pos, true);
+            data.push(combine(row, &rhs.rows[*pos]));
+        }
+        if found.is_empty() && kind.produce_null(false) {
+            data.push(combine(row, &rhs.nulls()));
+        }
+    }
+
+    if kind.produce_null(true) {
+        let nulls = lhs.nulls();
+        for (i, row) in rhs.rows.iter().enumerate() {
+            if !matched[i] {
+                data.push(combine(&nulls, row));
+            }
+        }
+    }
+
+    //The side that get filled with nulls become nullable
+    let side = |of: &Schema, nulls: bool| if nulls { of.nullable() } else { of.clone() };
+    let lhs_schema = side(&lhs.schema, kind.produce_null(true));
+    let rhs_schema = side(&rhs.schema, kind.produce_null(false));
+
+    Ok(Table::new(lhs_schema.extend(&rhs_schema), data))
+}
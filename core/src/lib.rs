@@ -1,8 +1,13 @@
-//pub mod btree;
+pub mod btree;
 pub mod dsl;
+pub mod error;
+pub mod group;
+pub mod join;
 pub mod macros;
+pub mod nest;
 pub mod range;
 pub mod relational;
+pub mod row;
 pub mod scalars;
 pub mod schema;
 pub mod sequence;
@@ -1,8 +1,17 @@
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use super::error::*;
 use super::types::*;
+
+use bit_vec::BitVec;
+use bytes::Bytes;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use decorum::R64;
 use rust_decimal::Decimal;
 
 macro_rules! convert {
-    ($kind:ident, $bound:path) => {
+    ($kind:ident, $bound:path, $data:path) => {
         impl<'a> From<&'a $kind> for Scalar {
             fn from(i: &'a $kind) -> Self {
                 $bound(i.clone())
@@ -24,20 +33,24 @@ macro_rules! convert {
             }
         }
 
-        impl From<Scalar> for $kind {
-            fn from(i: Scalar) -> Self {
+        impl TryFrom<Scalar> for $kind {
+            type Error = Error;
+
+            fn try_from(i: Scalar) -> ResultT<Self> {
                 match i {
-                    $bound(x) => x,
-                    _ => unreachable!("{:?}", i),
+                    $bound(x) => Ok(x),
+                    x => Err(Error::InvalidCast(x.kind(), $data)),
                 }
             }
         }
 
-        impl<'a> From<&'a Scalar> for $kind {
-            fn from(i: &'a Scalar) -> Self {
+        impl<'a> TryFrom<&'a Scalar> for $kind {
+            type Error = Error;
+
+            fn try_from(i: &'a Scalar) -> ResultT<Self> {
                 match i {
-                    $bound(x) => x.clone(),
-                    _ => unreachable!("{:?}", i),
+                    $bound(x) => Ok(x.clone()),
+                    x => Err(Error::InvalidCast(x.kind(), $data)),
                 }
             }
         }
@@ -53,13 +66,60 @@ macro_rules! convert {
     };
 }
 
-convert!(bool, Scalar::Bool);
-convert!(isize, Scalar::ISize);
-convert!(i32, Scalar::I32);
-convert!(i64, Scalar::I64);
-convert!(TimeStamp, Scalar::DateTime);
-convert!(Decimal, Scalar::Decimal);
-convert!(String, Scalar::UTF8);
+convert!(bool, Scalar::Bool, DataType::Bool);
+convert!(isize, Scalar::ISize, DataType::ISize);
+convert!(i32, Scalar::I32, DataType::I32);
+convert!(i64, Scalar::I64, DataType::I64);
+convert!(R64, Scalar::F64, DataType::F64);
+convert!(NaiveDate, Scalar::Date, DataType::Date);
+convert!(NaiveTime, Scalar::Time, DataType::Time);
+convert!(TimeStamp, Scalar::DateTime, DataType::DateTime);
+convert!(Duration, Scalar::Duration, DataType::Duration);
+convert!(Decimal, Scalar::Decimal, DataType::Decimal);
+convert!(String, Scalar::UTF8, DataType::UTF8);
+
+/// Like convert!, for the values stored behind a Rc
+macro_rules! convert_rc {
+    ($kind:ident, $bound:path, $data:expr) => {
+        impl From<$kind> for Scalar {
+            fn from(i: $kind) -> Self {
+                $bound(Rc::new(i))
+            }
+        }
+
+        impl TryFrom<Scalar> for $kind {
+            type Error = Error;
+
+            fn try_from(i: Scalar) -> ResultT<Self> {
+                match i {
+                    $bound(x) => Ok(Rc::try_unwrap(x).unwrap_or_else(|x| (*x).clone())),
+                    x => Err(Error::InvalidCast(x.kind(), $data)),
+                }
+            }
+        }
+
+        impl<'a> TryFrom<&'a Scalar> for $kind {
+            type Error = Error;
+
+            fn try_from(i: &'a Scalar) -> ResultT<Self> {
+                match i {
+                    $bound(x) => Ok((**x).clone()),
+                    x => Err(Error::InvalidCast(x.kind(), $data)),
+                }
+            }
+        }
+    };
+}
+
+convert_rc!(BitVec, Scalar::BitVec, DataType::BitVec);
+convert_rc!(Bytes, Scalar::Blob, DataType::Blob);
+convert_rc!(Variant, Scalar::Sum, DataType::Sum(vec![]));
+
+impl<'a> From<&'a str> for Scalar {
+    fn from(i: &'a str) -> Self {
+        Scalar::UTF8(i.to_string())
+    }
+}
 
 macro_rules! convert_rel {
     ($kind:ident, $bound:path) => {
@@ -82,3 +142,5 @@ convert_rel!(Vector, Rel::Vector);
 convert_rel!(Range, Rel::Range);
 convert_rel!(Seq, Rel::Seq);
 convert_rel!(Table, Rel::Table);
+convert_rel!(Row, Rel::Row);
+convert_rel!(BTree, Rel::KV);
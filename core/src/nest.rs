@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::*;
+use crate::relational::*;
+use crate::types::*;
+
+/// One row per distinct value of the `by` columns, with the other columns of
+/// the rows of the group as a relation in the column `name`. The groups are
+/// returned in the order they were first seen.
+pub fn nest<T: Relation + ?Sized>(of: &T, by: &[ColumnName], name: &str) -> ResultT<Table> {
+    let schema = of.schema();
+    let keys = schema.resolve_pos_many(by)?;
+    let rest = schema.except(&keys);
+    let inner = schema.only(&rest);
+
+    let mut fields = schema.only(&keys).columns;
+    fields.push(Field::new(name, DataType::Rel(inner.clone())).not_null());
+
+    let mut order: Vec<Col> = Vec::new();
+    let mut groups: HashMap<Col, Vec<Col>> = HashMap::new();
+
+    let mut iter = of.as_seq().iter()?;
+    while let Some(row) = iter.next() {
+        let key = project_row(&keys, &row);
+        let rows = groups.entry(key.clone()).or_insert_with(|| {
+            order.push(key);
+            Vec::new()
+        });
+        rows.push(project_row(&rest, &row));
+    }
+    if let Some(e) = iter.error() {
+        return Err(e);
+    }
+
+    let data = order.into_iter().map(|key| {
+        let rows = groups.remove(&key).unwrap();
+        let nested = Table::new(inner.clone(), rows);
+        let mut row = key;
+        row.push(Scalar::Rel(Rc::new(nested.into())));
+        row
+    });
+
+    Ok(Table::new(Schema::new(fields), data.collect()))
+}
+
+/// The inverse of nest: one row per row of the nested relation, with his
+/// columns in place of `col`. A null or empty relation drop the row
+pub fn unnest<T: Relation + ?Sized>(of: &T, col: &ColumnName) -> ResultT<Table> {
+    let schema = of.schema();
+    let pos = schema.resolve_pos(col)?;
+    let inner = match &schema[pos].kind {
+        DataType::Rel(inner) => inner.clone(),
+        x => return Err(Error::Unsupported("unnest".into(), x.clone())),
+    };
+
+    let mut fields = schema.columns.clone();
+    fields.splice(pos..=pos, inner.columns.iter().cloned());
+
+    let mut data = Vec::new();
+    let mut iter = of.as_seq().iter()?;
+    while let Some(row) = iter.next() {
+        let nested = match &row[pos] {
+            Scalar::None => continue,
+            Scalar::Rel(x) => aligned_rows(&inner, x)?,
+            x => return Err(Error::TypeMismatch(x.kind(), schema[pos].kind.clone())),
+        };
+        for values in nested {
+            let mut flat = row.clone();
+            flat.splice(pos..=pos, values);
+            data.push(flat);
+        }
+    }
+    if let Some(e) = iter.error() {
+        return Err(e);
+    }
+
+    Ok(Table::new(Schema::new(fields), data))
+}
@@ -1,12 +1,23 @@
 use std::cmp;
+use std::convert::TryFrom;
 use std::fmt;
+use std::rc::Rc;
+
+use rust_decimal::prelude::ToPrimitive;
 
 use crate::dsl::*;
+use crate::error::*;
+use crate::relational::*;
+use crate::stdlib::math::{cast_num, promote_kind};
 use crate::types::*;
 
 impl Relation for Range {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
     fn shape(&self) -> Shape {
-        Shape::Table(1, self.end - self.start)
+        Shape::Vector(self.count())
     }
 
     fn rows(&self) -> RowsIter<Self>
@@ -17,50 +28,166 @@ impl Relation for Range {
     }
 
     fn as_seq(&self) -> Seq {
-        Seq::new(self.schema.clone(), &self.shape(), ref_cell(self.rows()))
+        Seq::of_rows(self.schema.clone(), &self.shape(), self.clone())
     }
 
-    fn filter(&self, cmp: CmOp) -> Rel {
-        self.clone().into()
+    //Narrow the bounds when the comparison allow it
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(1)?;
+        let pred = promote_pred(pred)?;
+        let cmp = match pred {
+            Predicate::Cmp(cmp) => cmp,
+            Predicate::Logic(LogicOp::And, of) => {
+                let mut rel: Rel = self.clone().into();
+                for x in of {
+                    rel = rel.filter(x)?;
+                }
+                return Ok(rel);
+            }
+            pred => return self.to_vector().filter(pred),
+        };
+        let value = match *cmp.rhs {
+            Scalar::ISize(x) => x as i64,
+            _ => return self.to_vector().filter(cmp.into()),
+        };
+        let (start, end) = (self.start as i64, self.end as i64);
+        let step = self.step as i64;
+        //The first value of the range that is >= x
+        let from = |x: i64| {
+            if x <= start {
+                start
+            } else {
+                start + (x - start + step - 1) / step * step
+            }
+        };
+
+        let (lo, hi) = match cmp.op {
+            CompareOp::Less => (start, value),
+            CompareOp::LessEq => (start, value.saturating_add(1)),
+            CompareOp::Greater => (from(value.saturating_add(1)), end),
+            CompareOp::GreaterEq => (from(value), end),
+            CompareOp::Eq if from(value) == value => (value, value.saturating_add(1)),
+            CompareOp::Eq => (start, start),
+            CompareOp::NotEq => return self.to_vector().filter(cmp.into()),
+        };
+        let lo = cmp::min(lo, end);
+        let hi = cmp::max(lo, cmp::min(hi, end));
+
+        let mut range = self.clone();
+        range.start = lo as usize;
+        range.end = hi as usize;
+        Ok(range.into())
     }
 
-    fn union(&self, other: &Rel) -> Rel {
+    //Only stay a range when the other continue where this end, else the
+    //rows are concatenated like for the other relations
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        let next = self.start + self.count() * self.step;
         match other {
-            Rel::Range(b) => Self::new(
-                cmp::min(self.start, b.start),
-                cmp::max(self.end, b.end),
-                cmp::min(self.step, b.step),
-            )
-            .into(),
-            _ => unimplemented!(),
+            Rel::Range(b) if b.step == self.step && b.start == next => {
+                align_positions(&self.schema, &b.schema)?;
+                let mut range = self.clone();
+                range.end = cmp::max(self.end, b.end);
+                Ok(range.into())
+            }
+            _ => self.to_vector().union(other),
         }
     }
 
-    fn diff(&self, other: &Rel) -> Rel {
-        unimplemented!()
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        self.to_vector().diff(other)
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        match other {
+            Rel::Range(b) if self.is_aligned(b) => {
+                align_positions(&self.schema, &b.schema)?;
+                let mut range = self.clone();
+                range.start = cmp::max(self.start, b.start);
+                range.end = cmp::max(range.start, cmp::min(self.end, b.end));
+                Ok(range.into())
+            }
+            _ => self.to_vector().intersect(other),
+        }
     }
-    fn intersect(&self, other: &Rel) -> Rel {
-        unimplemented!()
+
+    //A range is always ascending and without nulls, so only the first key
+    //can change the order. The others are resolved to check them
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        sort_positions(&self.schema, keys)?;
+        match keys.first() {
+            Some(key) if !key.asc => {
+                let data = (self.start..self.end)
+                    .step_by(self.step)
+                    .rev()
+                    .map(|x| Scalar::ISize(x as isize))
+                    .collect();
+                Ok(Vector::new(self.schema.clone(), data).into())
+            }
+            _ => Ok(self.clone().into()),
+        }
+    }
+
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        let start = self.start.saturating_add(skip.saturating_mul(self.step));
+        let start = cmp::min(start, self.end);
+        let end = start.saturating_add(take.saturating_mul(self.step));
+        let end = cmp::min(end, self.end);
+
+        let mut range = self.clone();
+        range.start = start;
+        range.end = end;
+        Ok(range.into())
+    }
+
+    //A range never repeat a value
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        distinct_positions(&self.schema, on)?;
+        Ok(self.clone().into())
+    }
+
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        let pos = self.schema.resolve_pos_many(cols)?;
+        if pos == [0] {
+            Ok(self.clone().into())
+        } else {
+            self.as_seq().project(cols)
+        }
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        let mut range = self.clone();
+        range.schema = rename_schema(&self.schema, change)?;
+        Ok(range.into())
     }
 }
 
 impl Range {
+    /// Panic with a step of 0, use try_new for a step from the outside
     pub fn new(start: usize, end: usize, step: usize) -> Self {
-        let schema = schema(&[
-            ("start", DataType::ISize),
-            ("end", DataType::ISize),
-            ("step", DataType::ISize),
-        ]);
-        Range {
+        match Self::try_new(start, end, step) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like new, but a step of 0 is a error
+    pub fn try_new(start: usize, end: usize, step: usize) -> ResultT<Self> {
+        if step == 0 {
+            return Err(Error::Domain("a range with a step of 0".into()));
+        }
+        let schema = schema_it(DataType::ISize);
+        Ok(Range {
             schema,
             start,
             end,
             step,
-        }
+        })
     }
 
-    fn get(&mut self, pos: usize) -> Option<usize> {
-        if pos >= self.start && pos <= self.end {
+    /// The value if is one of the range
+    pub fn get(&self, pos: usize) -> Option<usize> {
+        if pos >= self.start && pos < self.end && (pos - self.start).is_multiple_of(self.step) {
             Some(pos)
         } else {
             None
@@ -68,6 +195,89 @@ impl Range {
     }
 }
 
+impl Range {
+    /// Same step & the same values where overlap
+    fn is_aligned(&self, other: &Range) -> bool {
+        let (a, b) = (
+            cmp::min(self.start, other.start),
+            cmp::max(self.start, other.start),
+        );
+        self.step == other.step && (b - a) % self.step == 0
+    }
+
+    pub fn to_vector(&self) -> Vector {
+        let data = (self.start..self.end)
+            .step_by(self.step)
+            .map(|x| Scalar::ISize(x as isize));
+        Vector::new(self.schema.clone(), data.collect())
+    }
+
+    pub fn count(&self) -> usize {
+        if self.end > self.start {
+            (self.end - self.start).div_ceil(self.step)
+        } else {
+            0
+        }
+    }
+}
+
+/// The integer bound that select the same integers than the comparison with
+/// a float or decimal, like `x < 2.5` is `x < 3`. None if there is not one,
+/// like for `x = 2.5`
+fn integer_bound(op: CompareOp, x: &Scalar) -> Option<isize> {
+    let (floor, ceil) = match x {
+        Scalar::F64(a) => {
+            let (floor, ceil) = (a.into_inner().floor(), a.into_inner().ceil());
+            //Saturated, but only exact for a equality
+            let exact = floor == ceil && floor == floor as isize as f64;
+            match op {
+                CompareOp::Eq | CompareOp::NotEq if !exact => return None,
+                _ => (floor as isize, ceil as isize),
+            }
+        }
+        Scalar::Decimal(a) => (a.floor().to_isize()?, a.ceil().to_isize()?),
+        _ => return None,
+    };
+    match op {
+        CompareOp::Less | CompareOp::GreaterEq => Some(ceil),
+        CompareOp::LessEq | CompareOp::Greater => Some(floor),
+        CompareOp::Eq | CompareOp::NotEq if floor == ceil => Some(floor),
+        CompareOp::Eq | CompareOp::NotEq => None,
+    }
+}
+
+/// The numeric constants of the comparisons as ISize, like the values of a
+/// range, so the narrowed bounds & the filter of the vector agree
+fn promote_pred(pred: Predicate) -> ResultT<Predicate> {
+    let pred = match pred {
+        Predicate::Cmp(mut cmp) => {
+            let rhs = match (&*cmp.rhs, promote_kind(DataType::ISize, cmp.rhs.kind())) {
+                (Scalar::I64(x), Ok(DataType::I64)) => isize::try_from(*x)
+                    .map(Scalar::ISize)
+                    .map_err(|_| Error::Overflow(DataType::ISize))?,
+                (x, Ok(DataType::ISize)) => cast_num(x, DataType::ISize)?,
+                (x, Ok(DataType::F64)) | (x, Ok(DataType::Decimal)) => {
+                    match integer_bound(cmp.op, x) {
+                        Some(x) => Scalar::ISize(x),
+                        None => return Ok(Predicate::Cmp(cmp)),
+                    }
+                }
+                _ => return Ok(Predicate::Cmp(cmp)),
+            };
+            cmp.rhs = Rc::new(rhs);
+            Predicate::Cmp(cmp)
+        }
+        Predicate::Logic(op, of) => {
+            let of = of.into_iter().map(promote_pred).collect::<ResultT<_>>()?;
+            Predicate::Logic(op, of)
+        }
+        Predicate::Not(of) => Predicate::negate(promote_pred(*of)?),
+        Predicate::ThreeValued(of) => Predicate::three_valued(promote_pred(*of)?),
+        x => x,
+    };
+    Ok(pred)
+}
+
 impl RelIter for RowsIter<Range> {
     fn pos(&self) -> usize {
         self.pos
@@ -84,7 +294,8 @@ impl RelIter for RowsIter<Range> {
     }
 
     fn row(&mut self) -> Col {
-        vec![Scalar::ISize(self.pos as isize)]
+        let pos = self.pos - self.rel.step;
+        vec![Scalar::ISize(pos as isize)]
     }
 }
 
@@ -97,4 +308,4 @@ impl fmt::Display for Range {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
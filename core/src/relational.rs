@@ -1,12 +1,177 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::error::*;
 use crate::types::*;
 
+/// Resolve the column of each sort key against the schema
+pub fn sort_positions(schema: &Schema, keys: &[SortKey]) -> ResultT<Vec<(usize, SortKey)>> {
+    keys.iter()
+        .map(|x| Ok((schema.resolve_pos(&x.col)?, x.clone())))
+        .collect()
+}
+
+pub fn compare_rows(keys: &[(usize, SortKey)], a: &[Scalar], b: &[Scalar]) -> Ordering {
+    for (pos, key) in keys {
+        match key.compare(&a[*pos], &b[*pos]) {
+            Ordering::Equal => continue,
+            x => return x,
+        }
+    }
+    Ordering::Equal
+}
+
+pub fn project_row(pos: &[usize], row: &[Scalar]) -> Col {
+    pos.iter().map(|x| row[*x].clone()).collect()
+}
+
+/// Pull all the rows of the relation
+pub fn to_rows<T: Relation + ?Sized>(of: &T) -> ResultT<Vec<Col>> {
+    of.as_seq().iter()?.rows_left()
+}
+
+/// The schema with the computed columns added, and the expressions resolved
+pub fn extend_schema(
+    schema: &Schema,
+    cols: &[(String, RowExpr)],
+) -> ResultT<(Schema, Vec<RowExpr>)> {
+    let mut fields = schema.columns.clone();
+    let mut exprs = Vec::with_capacity(cols.len());
+
+    for (name, expr) in cols {
+        if fields.iter().any(|x| &x.name == name) {
+            return Err(Error::DuplicateField(name.clone()));
+        }
+        fields.push(Field::new(name, expr.kind(schema)?));
+        exprs.push(expr.resolve(schema)?);
+    }
+
+    Ok((Schema::new(fields), exprs))
+}
+
+pub fn extend_row(exprs: &[RowExpr], row: Col, mode: OnError) -> ResultT<Col> {
+    let extra = exprs
+        .iter()
+        .map(|x| x.eval_with(&row, mode))
+        .collect::<ResultT<Col>>()?;
+    let mut row = row;
+    row.extend(extra);
+    Ok(row)
+}
+
+/// Change the column names, as pairs of old column & new name
+pub fn rename_schema(schema: &Schema, change: &[(ColumnName, String)]) -> ResultT<Schema> {
+    let change: Vec<_> = change
+        .iter()
+        .map(|(col, name)| (col.clone(), name.as_str()))
+        .collect();
+    schema.rename(&change)
+}
+
+/// The columns to check for distinct, all of them if none is given
+pub fn distinct_positions(schema: &Schema, on: &[ColumnName]) -> ResultT<Pos> {
+    if on.is_empty() {
+        Ok((0..schema.len()).collect())
+    } else {
+        schema.resolve_pos_many(on)
+    }
+}
+
+/// Keep the first occurrence of each key, preserving the order
+pub fn distinct_rows<T, F>(rows: &[T], key: F) -> Vec<T>
+where
+    T: Clone,
+    F: Fn(&T) -> Col,
+{
+    let mut seen = HashSet::new();
+    rows.iter()
+        .filter(|x| seen.insert(key(x)))
+        .cloned()
+        .collect()
+}
+
+/// The rows left after skip & take
+pub fn limit_rows<T: Clone>(rows: &[T], skip: usize, take: usize) -> Vec<T> {
+    rows.iter().skip(skip).take(take).cloned().collect()
+}
+
+/// Pull the rows of other with the columns in the order of the schema. Like
+/// the schemas, is only valid if have the same fields in any order.
+pub fn aligned_rows(schema: &Schema, other: &Rel) -> ResultT<Vec<Col>> {
+    let pos = align_positions(schema, &other.schema())?;
+    let rows = to_rows(other)?;
+    if pos.iter().enumerate().all(|(i, x)| i == *x) {
+        Ok(rows)
+    } else {
+        Ok(rows.iter().map(|x| project_row(&pos, x)).collect())
+    }
+}
+
+/// For each column of the schema, his position in other
+pub fn align_positions(schema: &Schema, other: &Schema) -> ResultT<Pos> {
+    if schema != other {
+        return Err(Error::SchemaMismatch(schema.clone(), other.clone()));
+    }
+    let names: Vec<_> = schema.columns.iter().map(|x| &x.name).collect();
+    let same = other
+        .columns
+        .iter()
+        .zip(names.iter())
+        .all(|(x, n)| &x.name == *n);
+    if same {
+        Ok((0..schema.len()).collect())
+    } else {
+        Ok(names.iter().map(|x| other.named(x).unwrap().0).collect())
+    }
+}
+
+pub fn union_rows(lhs: &[Col], rhs: Vec<Col>) -> Vec<Col> {
+    let mut rows = lhs.to_vec();
+    rows.extend(rhs);
+    rows
+}
+
+/// Like sets, without duplicates and keeping the order of the left rows
+pub fn diff_rows(lhs: &[Col], rhs: Vec<Col>) -> Vec<Col> {
+    let rhs: HashSet<_> = rhs.into_iter().collect();
+    let rows: Vec<_> = lhs.iter().filter(|x| !rhs.contains(*x)).cloned().collect();
+    distinct_rows(&rows, |x| x.clone())
+}
+
+/// Like sets, without duplicates and keeping the order of the left rows
+pub fn intersect_rows(lhs: &[Col], rhs: Vec<Col>) -> Vec<Col> {
+    let rhs: HashSet<_> = rhs.into_iter().collect();
+    let rows: Vec<_> = lhs.iter().filter(|x| rhs.contains(*x)).cloned().collect();
+    distinct_rows(&rows, |x| x.clone())
+}
+
+pub fn sort_rows(keys: &[(usize, SortKey)], rows: &mut [Col]) {
+    rows.sort_by(|a, b| compare_rows(keys, a, b));
+}
+
 impl Relation for Rel {
+    fn schema(&self) -> Schema {
+        match self {
+            Rel::One(x) => x.schema(),
+            Rel::Row(x) => x.schema(),
+            Rel::Vector(x) => x.schema(),
+            Rel::KV(x) => x.schema(),
+            Rel::Range(x) => x.schema(),
+            Rel::Seq(x) => x.schema(),
+            Rel::Table(x) => x.schema(),
+        }
+    }
+
     fn shape(&self) -> Shape {
         match self {
             Rel::One(x) => x.shape(),
+            Rel::Row(x) => x.shape(),
             Rel::Vector(x) => x.shape(),
-            //Rel::Table(x) => x.shape(),
-            _ => unimplemented!(),
+            Rel::KV(x) => x.shape(),
+            Rel::Range(x) => x.shape(),
+            Rel::Seq(x) => x.shape(),
+            Rel::Table(x) => x.shape(),
         }
     }
 
@@ -20,73 +185,198 @@ impl Relation for Rel {
     fn as_seq(&self) -> Seq {
         match self {
             Rel::One(x) => x.as_seq(),
+            Rel::Row(x) => x.as_seq(),
             Rel::Vector(x) => x.as_seq(),
+            Rel::KV(x) => x.as_seq(),
             Rel::Range(x) => x.as_seq(),
             Rel::Seq(x) => x.as_seq(),
             Rel::Table(x) => x.as_seq(),
         }
     }
 
-    fn filter(&self, cmp: CmOp) -> Rel {
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
         match self {
-            Rel::One(x) => x.filter(cmp),
-            Rel::Vector(x) => x.filter(cmp),
-            Rel::Range(x) => x.filter(cmp),
-            Rel::Seq(x) => x.filter(cmp),
-            Rel::Table(x) => x.filter(cmp),
+            Rel::One(x) => x.filter(pred),
+            Rel::Row(x) => x.filter(pred),
+            Rel::Vector(x) => x.filter(pred),
+            Rel::KV(x) => x.filter(pred),
+            Rel::Range(x) => x.filter(pred),
+            Rel::Seq(x) => x.filter(pred),
+            Rel::Table(x) => x.filter(pred),
         }
     }
 
-    fn union(&self, other: &Rel) -> Rel {
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
         match self {
             Rel::One(x) => x.union(other),
+            Rel::Row(x) => x.union(other),
             Rel::Vector(x) => x.union(other),
+            Rel::KV(x) => x.union(other),
             Rel::Range(x) => x.union(other),
             Rel::Seq(x) => x.union(other),
             Rel::Table(x) => x.union(other),
         }
     }
 
-    fn diff(&self, other: &Rel) -> Rel {
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
         match self {
             Rel::One(x) => x.diff(other),
+            Rel::Row(x) => x.diff(other),
             Rel::Vector(x) => x.diff(other),
+            Rel::KV(x) => x.diff(other),
             Rel::Range(x) => x.diff(other),
             Rel::Seq(x) => x.diff(other),
             Rel::Table(x) => x.diff(other),
         }
     }
 
-    fn intersect(&self, other: &Rel) -> Rel {
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
         match self {
             Rel::One(x) => x.intersect(other),
+            Rel::Row(x) => x.intersect(other),
             Rel::Vector(x) => x.intersect(other),
+            Rel::KV(x) => x.intersect(other),
             Rel::Range(x) => x.intersect(other),
             Rel::Seq(x) => x.intersect(other),
             Rel::Table(x) => x.intersect(other),
         }
     }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        match self {
+            Rel::One(x) => x.sort(keys),
+            Rel::Row(x) => x.sort(keys),
+            Rel::Vector(x) => x.sort(keys),
+            Rel::KV(x) => x.sort(keys),
+            Rel::Range(x) => x.sort(keys),
+            Rel::Seq(x) => x.sort(keys),
+            Rel::Table(x) => x.sort(keys),
+        }
+    }
+
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        match self {
+            Rel::One(x) => x.limit(skip, take),
+            Rel::Row(x) => x.limit(skip, take),
+            Rel::Vector(x) => x.limit(skip, take),
+            Rel::KV(x) => x.limit(skip, take),
+            Rel::Range(x) => x.limit(skip, take),
+            Rel::Seq(x) => x.limit(skip, take),
+            Rel::Table(x) => x.limit(skip, take),
+        }
+    }
+
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        match self {
+            Rel::One(x) => x.distinct(on),
+            Rel::Row(x) => x.distinct(on),
+            Rel::Vector(x) => x.distinct(on),
+            Rel::KV(x) => x.distinct(on),
+            Rel::Range(x) => x.distinct(on),
+            Rel::Seq(x) => x.distinct(on),
+            Rel::Table(x) => x.distinct(on),
+        }
+    }
+
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        match self {
+            Rel::One(x) => x.project(cols),
+            Rel::Row(x) => x.project(cols),
+            Rel::Vector(x) => x.project(cols),
+            Rel::KV(x) => x.project(cols),
+            Rel::Range(x) => x.project(cols),
+            Rel::Seq(x) => x.project(cols),
+            Rel::Table(x) => x.project(cols),
+        }
+    }
+
+    fn extend(&self, cols: &[(String, RowExpr)], mode: OnError) -> ResultT<Rel> {
+        match self {
+            Rel::One(x) => x.extend(cols, mode),
+            Rel::Row(x) => x.extend(cols, mode),
+            Rel::Vector(x) => x.extend(cols, mode),
+            Rel::KV(x) => x.extend(cols, mode),
+            Rel::Range(x) => x.extend(cols, mode),
+            Rel::Seq(x) => x.extend(cols, mode),
+            Rel::Table(x) => x.extend(cols, mode),
+        }
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        match self {
+            Rel::One(x) => x.rename(change),
+            Rel::Row(x) => x.rename(change),
+            Rel::Vector(x) => x.rename(change),
+            Rel::KV(x) => x.rename(change),
+            Rel::Range(x) => x.rename(change),
+            Rel::Seq(x) => x.rename(change),
+            Rel::Table(x) => x.rename(change),
+        }
+    }
 }
 
 impl Rel {
-    pub fn query(self, query: &[Query]) -> Rel {
+    pub fn query(self, query: &[Query]) -> ResultT<Rel> {
         if query.is_empty() {
-            self.clone()
+            Ok(self)
         } else {
             let mut next = self;
             for q in query {
                 next = match q {
                     Query::Where(filter) => next.filter(filter.clone()),
                     Query::Set(query, other) => match query {
-                        SetQuery::Union => next.union(&other),
-                        SetQuery::Diff => next.diff(&other),
-                        SetQuery::Intersection => next.intersect(&other),
+                        SetQuery::Union => next.union(other),
+                        SetQuery::Diff => next.diff(other),
+                        SetQuery::Intersection => next.intersect(other),
                     },
-                    //Query::Sort(asc, pos) => next.sorted(*asc, *pos),
-                    _ => unimplemented!(),
-                };
+                    Query::Limit(skip, take) => next.limit(*skip, *take),
+                    Query::Distinct(on) => next.distinct(on),
+                    Query::Sort(keys) => next.sort(keys),
+                    Query::Select(cols) => next.project(cols),
+                    Query::Deselect(cols) => next.deselect(cols),
+                    Query::Join(kind, on, other) => next.join(other, *kind, on),
+                    Query::Group(by, aggs) => next.group(by, aggs),
+                    Query::Nest(by, name) => next.nest(by, name),
+                    Query::Unnest(col) => next.unnest(col),
+                    Query::Extend(cols, mode) => next.extend(cols, *mode),
+                    Query::Rename(change) => next.rename(change),
+                }?;
+            }
+            Ok(next)
+        }
+    }
+}
+
+//Like the literals of the language: [name:Type, ...; row; row]
+fn fmt_rows<'a, I>(f: &mut fmt::Formatter<'_>, schema: &Schema, rows: I) -> fmt::Result
+where
+    I: Iterator<Item = &'a [Scalar]>,
+{
+    write!(f, "[{}", schema)?;
+    for row in rows {
+        write!(f, ";")?;
+        for (i, x) in row.iter().enumerate() {
+            let sep = if i > 0 { ", " } else { " " };
+            write!(f, "{}{}", sep, x)?;
+        }
+    }
+    write!(f, "]")
+}
+
+impl fmt::Display for Rel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rel::One(x) => write!(f, "{}", x),
+            Rel::Row(x) => fmt_rows(f, &x.schema, std::iter::once(x.data.as_slice())),
+            Rel::Vector(x) => fmt_rows(f, &x.schema, x.data.iter().map(std::slice::from_ref)),
+            Rel::KV(x) => {
+                let rows: Vec<_> = x.data.iter().map(|(k, v)| [k.clone(), v.clone()]).collect();
+                fmt_rows(f, &x.schema, rows.iter().map(|x| &x[..]))
             }
-            next
+            Rel::Table(x) => fmt_rows(f, &x.schema, x.data.iter().map(|x| x.as_slice())),
+            //Not iterated, a stream can be consumed only once
+            Rel::Range(x) => write!(f, "{}", x),
+            Rel::Seq(x) => write!(f, "{}", x),
         }
     }
 }
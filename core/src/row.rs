@@ -0,0 +1,118 @@
+use crate::dsl::*;
+use crate::error::*;
+use crate::relational::*;
+use crate::types::*;
+
+impl Relation for Row {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
+    fn shape(&self) -> Shape {
+        Shape::Row(self.data.len())
+    }
+
+    fn rows(&self) -> RowsIter<Self>
+    where
+        Self: Sized,
+    {
+        RowsIter::new(self.clone())
+    }
+
+    fn as_seq(&self) -> Seq {
+        Seq::of_rows(self.schema.clone(), &self.shape(), self.clone())
+    }
+
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(self.schema.len())?;
+        if pred.eval(&self.data) {
+            Ok(self.clone().into())
+        } else {
+            Ok(self.to_empty_table().into())
+        }
+    }
+
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        self.to_table().union(other)
+    }
+
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        match self.to_table().diff(other)? {
+            Rel::Table(x) if !x.data.is_empty() => Ok(self.clone().into()),
+            x => Ok(x),
+        }
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        match self.to_table().intersect(other)? {
+            Rel::Table(x) if !x.data.is_empty() => Ok(self.clone().into()),
+            x => Ok(x),
+        }
+    }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        sort_positions(&self.schema, keys)?;
+        Ok(self.clone().into())
+    }
+
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        if skip == 0 && take > 0 {
+            Ok(self.clone().into())
+        } else {
+            Ok(self.to_empty_table().into())
+        }
+    }
+
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        distinct_positions(&self.schema, on)?;
+        Ok(self.clone().into())
+    }
+
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        let pos = self.schema.resolve_pos_many(cols)?;
+        let schema = self.schema.only(&pos);
+
+        Ok(Self::new(schema, project_row(&pos, &self.data)).into())
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        let schema = rename_schema(&self.schema, change)?;
+        Ok(Self::new(schema, self.data.clone()).into())
+    }
+}
+
+impl Row {
+    pub fn new(schema: Schema, data: Col) -> Self {
+        Row { schema, data }
+    }
+
+    /// With the types inferred & the columns named by position
+    pub fn new_scalars(data: &[Scalar]) -> Self {
+        let schema = Schema::generate(&infer_types(data));
+        Self::new(schema, data.to_vec())
+    }
+
+    pub fn to_table(&self) -> Table {
+        Table::new(self.schema.clone(), vec![self.data.clone()])
+    }
+
+    fn to_empty_table(&self) -> Table {
+        Table::new(self.schema.clone(), vec![])
+    }
+}
+
+impl RelIter for RowsIter<Row> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn advance(&mut self) -> bool {
+        let ok = self.pos < 1;
+        self.pos += 1;
+        ok
+    }
+
+    fn row(&mut self) -> Col {
+        self.rel.data.clone()
+    }
+}
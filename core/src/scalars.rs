@@ -1,15 +1,16 @@
+use std::convert::TryFrom;
 use std::fmt;
 
 use crate::dsl::schema_it;
+use crate::error::*;
+use crate::relational::{distinct_positions, sort_positions};
 use crate::types::*;
 
-impl Default for Scalar {
-    fn default() -> Scalar {
-        Scalar::None
+impl Relation for Scalar {
+    fn schema(&self) -> Schema {
+        schema_it(self.kind())
     }
-}
 
-impl Relation for Scalar {
     fn shape(&self) -> Shape {
         Shape::Scalar
     }
@@ -22,30 +23,60 @@ impl Relation for Scalar {
     }
 
     fn as_seq(&self) -> Seq {
-        Seq::new(schema_it(self.kind()), &self.shape(), ref_cell(self.rows()))
+        Seq::of_rows(schema_it(self.kind()), &self.shape(), self.clone())
     }
 
-    fn filter(&self, cmp: CmOp) -> Rel {
-        let apply = cmp.get_fn();
-        if apply(self, &cmp.rhs) {
-            self.clone().into()
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(1)?;
+        if pred.eval(std::slice::from_ref(self)) {
+            Ok(self.clone().into())
         } else {
-            self.to_empty_vector().into()
+            Ok(self.to_empty_vector().into())
         }
     }
 
-    fn union(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::One(b) => Vector::new_scalars(&[self.clone(), b.clone()]).into(),
-            _ => unimplemented!(),
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        self.to_vector().union(other)
+    }
+
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        match self.to_vector().diff(other)? {
+            Rel::Vector(x) if !x.is_empty() => Ok(self.clone().into()),
+            x => Ok(x),
+        }
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        match self.to_vector().intersect(other)? {
+            Rel::Vector(x) if !x.is_empty() => Ok(self.clone().into()),
+            x => Ok(x),
+        }
+    }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        sort_positions(&self.schema(), keys)?;
+        Ok(self.clone().into())
+    }
+
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        if skip == 0 && take > 0 {
+            Ok(self.clone().into())
+        } else {
+            Ok(self.to_empty_vector().into())
         }
     }
 
-    fn diff(&self, other: &Rel) -> Rel {
-        unimplemented!()
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        distinct_positions(&self.schema(), on)?;
+        Ok(self.clone().into())
+    }
+
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        self.to_vector().project(cols)
     }
-    fn intersect(&self, other: &Rel) -> Rel {
-        unimplemented!()
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        self.to_vector().rename(change)
     }
 }
 
@@ -55,13 +86,25 @@ impl Scalar {
     }
 
     pub fn to_vector(&self) -> Vector {
-        Vector::new_scalars(&[self.clone()])
+        Vector::new_scalars(std::slice::from_ref(self))
     }
 
     pub fn repeat(of: &Scalar, times: usize) -> Vec<Scalar> {
         vec![of.clone(); times]
     }
 
+    pub fn is_null(&self) -> bool {
+        *self == Scalar::None
+    }
+
+    /// The first value not null, or null
+    pub fn coalesce(of: &[Scalar]) -> Scalar {
+        of.iter()
+            .find(|x| !x.is_null())
+            .cloned()
+            .unwrap_or_default()
+    }
+
     pub fn kind(&self) -> DataType {
         match self {
             Scalar::None => DataType::None,
@@ -71,9 +114,16 @@ impl Scalar {
             Scalar::I64(_) => DataType::I64,
             Scalar::F64(_) => DataType::F64,
             Scalar::Decimal(_) => DataType::Decimal,
+            Scalar::Date(_) => DataType::Date,
+            Scalar::Time(_) => DataType::Time,
             Scalar::DateTime(_) => DataType::DateTime,
+            Scalar::Duration(_) => DataType::Duration,
             Scalar::UTF8(_) => DataType::UTF8,
-            Scalar::Rel(_) => DataType::Rel,
+            Scalar::BitVec(_) => DataType::BitVec,
+            Scalar::Blob(_) => DataType::Blob,
+            Scalar::Sum(x) => DataType::Sum(vec![(x.tag.clone(), x.value.kind())]),
+            Scalar::Product(x) => DataType::Product(x.iter().map(Scalar::kind).collect()),
+            Scalar::Rel(x) => DataType::Rel(x.schema()),
         }
     }
 }
@@ -90,26 +140,25 @@ impl RelIter for RowsIter<Scalar> {
     }
 
     fn row(&mut self) -> Col {
-        vec![self.rel.clone()]
+        vec![self.rel.as_ref().clone()]
     }
 }
 
 pub fn bin_op<T, Op>(op: Op, x: T, y: T) -> Scalar
 where
     Op: FnOnce(T, T) -> T,
-    T: From<Scalar>,
     Scalar: From<T>,
 {
     op(x, y).into()
 }
 
-pub fn bin_op_by<T, Op>(op: Op, x: Scalar, y: Scalar) -> Scalar
+pub fn bin_op_by<T, Op>(op: Op, x: Scalar, y: Scalar) -> ResultT<Scalar>
 where
     Op: FnOnce(T, T) -> T,
-    T: From<Scalar>,
+    T: TryFrom<Scalar, Error = Error>,
     Scalar: From<T>,
 {
-    bin_op(op, x.into(), y.into())
+    Ok(bin_op(op, T::try_from(x)?, T::try_from(y)?))
 }
 
 impl fmt::Display for Scalar {
@@ -122,9 +171,40 @@ impl fmt::Display for Scalar {
             Scalar::I64(x) => write!(f, "{}", x),
             Scalar::F64(x) => write!(f, "{}", x),
             Scalar::Decimal(x) => write!(f, "{}", x),
+            Scalar::Date(x) => write!(f, "{}", x),
+            Scalar::Time(x) => write!(f, "{}", x),
             Scalar::DateTime(x) => write!(f, "{}", x),
+            Scalar::Duration(x) => write!(f, "{}", x),
             Scalar::UTF8(x) => write!(f, "{}", x),
-            _ => unimplemented!(),
+            Scalar::BitVec(x) => {
+                write!(f, "0b")?;
+                x.iter().try_for_each(|bit| write!(f, "{}", bit as u8))
+            }
+            Scalar::Blob(x) => {
+                write!(f, "0x")?;
+                x.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
+            Scalar::Sum(x) => write!(f, "{}", x),
+            Scalar::Product(x) => {
+                write!(f, "(")?;
+                for (i, value) in x.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Scalar::Rel(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Scalar::None => write!(f, "{}", self.tag),
+            _ => write!(f, "{}({})", self.tag, self.value),
         }
     }
 }
@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::Index;
@@ -5,18 +6,36 @@ use std::ops::Index;
 use self::bit_vec::BitVec;
 use bit_vec;
 
+use crate::error::*;
+use crate::stdlib::math::cast_num;
 use crate::types::*;
 
 impl Field {
+    /// A nullable field, like all the columns unless marked with not_null
     pub fn new(name: &str, kind: DataType) -> Self {
+        Self::new_owned(name.to_string(), kind)
+    }
+
+    pub fn new_owned(name: String, kind: DataType) -> Self {
         Field {
-            name: name.to_string(),
+            name,
             kind,
+            nullable: true,
         }
     }
 
-    pub fn new_owned(name: String, kind: DataType) -> Self {
-        Field { name, kind }
+    pub fn not_null(self) -> Self {
+        Field {
+            nullable: false,
+            ..self
+        }
+    }
+
+    pub fn nullable(self) -> Self {
+        Field {
+            nullable: true,
+            ..self
+        }
     }
 
     pub fn name(&self) -> &String {
@@ -26,6 +45,29 @@ impl Field {
     pub fn kind(&self) -> &DataType {
         &self.kind
     }
+
+    /// Check the value can be stored in the field: is of his type, or null if
+    /// nullable. With `implicit`, a number is casted up to the type, like a
+    /// I32 into a I64
+    pub fn conform(&self, value: Scalar, row: usize, implicit: bool) -> ResultT<Scalar> {
+        let kind = value.kind();
+        if value.is_null() {
+            return if self.nullable {
+                Ok(value)
+            } else {
+                Err(Error::NullNotAllowed(self.name.clone(), row))
+            };
+        }
+        if self.kind.accepts(&kind) {
+            return Ok(value);
+        }
+        let casted = if implicit {
+            cast_num(&value, self.kind.clone())
+        } else {
+            Err(Error::InvalidCast(kind, self.kind.clone()))
+        };
+        casted.map_err(|e| Error::InvalidValue(row, self.name.clone(), Box::new(e)))
+    }
 }
 
 impl Schema {
@@ -46,7 +88,7 @@ impl Schema {
         let mut names = Vec::with_capacity(types.len());
 
         for (pos, kind) in types.iter().enumerate() {
-            names.push(Field::new_owned(pos.to_string(), *kind));
+            names.push(Field::new_owned(pos.to_string(), kind.clone()));
         }
 
         Self::new(names)
@@ -71,35 +113,25 @@ impl Schema {
     }
 
     ///Recover the column position from the relative ColumnName
-    pub fn resolve_pos(&self, of: &ColumnName) -> usize {
+    pub fn resolve_pos(&self, of: &ColumnName) -> ResultT<usize> {
         match of {
-            ColumnName::Pos(x) => *x,
-            ColumnName::Name(x) => {
-                let (pos, _f) = self.named(x).unwrap();
-                pos
-            }
+            ColumnName::Pos(x) if *x < self.len() => Ok(*x),
+            ColumnName::Pos(x) => Err(Error::ColumnOutOfRange(*x, self.len())),
+            ColumnName::Name(x) => match self.named(x) {
+                Some((pos, _f)) => Ok(pos),
+                None => Err(Error::FieldNotFound(x.clone())),
+            },
         }
     }
 
-    pub fn resolve_pos_many(&self, of: &[ColumnName]) -> Pos {
+    pub fn resolve_pos_many(&self, of: &[ColumnName]) -> ResultT<Pos> {
         of.iter().map(|x| self.resolve_pos(x)).collect()
     }
 
     ///Recover the column names from a list of relative ColumnName
-    pub fn resolve_names(&self, of: &[ColumnName]) -> Schema {
-        let mut names = Vec::with_capacity(of.len());
-
-        for name in of.iter() {
-            let pick = match name {
-                ColumnName::Pos(x) => self.columns[*x].clone(),
-                ColumnName::Name(x) => {
-                    let (_pos, f) = self.named(x).unwrap();
-                    f.clone()
-                }
-            };
-            names.push(pick);
-        }
-        Self::new(names)
+    pub fn resolve_names(&self, of: &[ColumnName]) -> ResultT<Schema> {
+        let pos = self.resolve_pos_many(of)?;
+        Ok(self.only(&pos))
     }
 
     pub fn join(&self, other: &Self) -> Vec<usize> {
@@ -140,6 +172,30 @@ impl Schema {
         pos
     }
 
+    /// The same fields, all nullable. Like the side of a outer join
+    pub fn nullable(&self) -> Self {
+        Self::new(self.columns.iter().cloned().map(Field::nullable).collect())
+    }
+
+    /// Check the row at `pos` has a value for each field, that conform to it
+    pub fn conform(&self, row: Col, pos: usize, implicit: bool) -> ResultT<Col> {
+        if row.len() != self.len() {
+            return Err(Error::ArityMismatch(pos, self.len(), row.len()));
+        }
+        row.into_iter()
+            .zip(self.columns.iter())
+            .map(|(value, field)| field.conform(value, pos, implicit))
+            .collect()
+    }
+
+    /// Conform all the rows, failing on the first bad one
+    pub fn conform_rows(&self, rows: Vec<Col>, implicit: bool) -> ResultT<Vec<Col>> {
+        rows.into_iter()
+            .enumerate()
+            .map(|(pos, row)| self.conform(row, pos, implicit))
+            .collect()
+    }
+
     pub fn deselect(&self, remove: &[usize]) -> Self {
         let deselect = self.except(remove);
         self.only(deselect.as_slice())
@@ -163,7 +219,7 @@ impl Schema {
         for f in _right {
             if self.exist(&f.name) {
                 let name = format!("{}_{}", f.name, cont);
-                fields.push(Field::new(&name, f.kind));
+                fields.push(Field { name, ..f });
                 cont += 1;
             } else {
                 fields.push(f);
@@ -173,16 +229,20 @@ impl Schema {
         Self::new(fields)
     }
 
-    pub fn rename(&self, change: &[(ColumnName, &str)]) -> Self {
+    pub fn rename(&self, change: &[(ColumnName, &str)]) -> ResultT<Self> {
         let mut names = self.columns.clone();
 
         for (col, name) in change {
-            let pos = self.resolve_pos(&col);
-            let old = names[pos].kind;
-            names[pos] = Field::new(name, old);
+            let pos = self.resolve_pos(col)?;
+            names[pos].name = name.to_string();
+        }
+        for (i, x) in names.iter().enumerate() {
+            if names[..i].iter().any(|y| y.name == x.name) {
+                return Err(Error::DuplicateField(x.name.clone()));
+            }
         }
 
-        Self::new(names)
+        Ok(Self::new(names))
     }
 }
 
@@ -194,27 +254,40 @@ impl Index<usize> for Schema {
     }
 }
 
+impl Schema {
+    //The nullability is not part of the identity, so a column without nulls
+    //is compatible with a nullable one
+    fn sorted_types(&self) -> Vec<(&String, &DataType)> {
+        let mut of: Vec<_> = self.columns.iter().map(|x| (&x.name, &x.kind)).collect();
+        of.sort();
+        of
+    }
+}
+
 impl PartialEq for Schema {
     fn eq(&self, other: &Schema) -> bool {
-        if self.columns.len() == other.columns.len() {
-            let mut a = self.columns.clone();
-            let mut b = other.columns.clone();
-            a.sort();
-            b.sort();
-            a == b
-        } else {
-            false
-        }
+        self.columns.len() == other.columns.len() && self.sorted_types() == other.sorted_types()
     }
 }
 
 impl Eq for Schema {}
 
+//On the same key of Eq & Hash, so the schemas equal are ordered as equal
+impl PartialOrd for Schema {
+    fn partial_cmp(&self, other: &Schema) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Schema {
+    fn cmp(&self, other: &Schema) -> Ordering {
+        self.sorted_types().cmp(&other.sorted_types())
+    }
+}
+
 impl Hash for Schema {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        let mut a = self.columns.clone();
-        a.sort();
-        a.hash(state);
+        self.sorted_types().hash(state);
     }
 }
 
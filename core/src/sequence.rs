@@ -1,12 +1,20 @@
 use std::cell::RefCell;
+use std::cmp;
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 
+use crate::error::*;
+use crate::relational::*;
 use crate::types::*;
 
 impl Relation for Seq {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
     fn shape(&self) -> Shape {
         self.shape
     }
@@ -22,112 +30,657 @@ impl Relation for Seq {
         self.clone()
     }
 
-    fn filter(&self, cmp: CmOp) -> Rel {
-        unimplemented!()
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(self.schema.len())?;
+        let seq = self.derive(self.schema.clone(), &self.shape, move |iter| {
+            let pred = pred.clone();
+            Ok(Box::new(FilterIter { pred, iter }))
+        });
+        Ok(seq.into())
     }
 
-    fn union(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::Seq(b) => {
-                Self::of_union(&self.schema, &self.shape, self.iter.clone(), b.iter.clone()).into()
-            }
-            _ => unimplemented!(),
-        }
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        let other = self.aligned(other)?;
+        let (_, a) = self.shape.size();
+        let (_, b) = other.shape.size();
+        //The keys can repeat after a union, so is not longer a KV
+        let shape = match (self.shape, other.shape) {
+            (_, Shape::Stream(_)) => Shape::Stream(self.schema.len()),
+            (Shape::KV(_), _) => Shape::Table(2, a + b),
+            (shape, _) => shape.with_rows(a + b),
+        };
+
+        Ok(Self::of_union(&self.schema, &shape, self, &other).into())
+    }
+
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        Ok(self.of_set(other, false)?.into())
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        Ok(self.of_set(other, true)?.into())
+    }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        let keys = sort_positions(&self.schema, keys)?;
+        //A KV is always in key order, so the sorted rows are a table
+        let shape = match self.shape {
+            Shape::KV(rows) => Shape::Table(2, rows),
+            shape => shape,
+        };
+        let seq = self.derive(self.schema.clone(), &shape, move |iter| {
+            Ok(Box::new(SortIter {
+                keys: keys.clone(),
+                iter,
+                rows: None,
+                pos: 0,
+            }))
+        });
+        Ok(seq.into())
+    }
+
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        let (_, rows) = self.shape.size();
+        let shape = self
+            .shape
+            .with_rows(cmp::min(rows.saturating_sub(skip), take));
+        let seq = self.derive(self.schema.clone(), &shape, move |iter| {
+            Ok(Box::new(LimitIter { skip, take, iter }))
+        });
+        Ok(seq.into())
     }
 
-    fn diff(&self, other: &Rel) -> Rel {
-        unimplemented!()
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        let pos = distinct_positions(&self.schema, on)?;
+        let seq = self.derive(self.schema.clone(), &self.shape, move |iter| {
+            Ok(Box::new(DistinctIter {
+                pos: pos.clone(),
+                seen: HashSet::new(),
+                iter,
+            }))
+        });
+        Ok(seq.into())
     }
-    fn intersect(&self, other: &Rel) -> Rel {
-        unimplemented!()
+
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        let pos = self.schema.resolve_pos_many(cols)?;
+        let schema = self.schema.only(&pos);
+        let shape = self.shape.with_cols(pos.len());
+        let seq = self.derive(schema, &shape, move |iter| {
+            Ok(Box::new(ProjectIter {
+                pos: pos.clone(),
+                iter,
+            }))
+        });
+        Ok(seq.into())
+    }
+
+    fn extend(&self, cols: &[(String, RowExpr)], mode: OnError) -> ResultT<Rel> {
+        let (schema, exprs) = extend_schema(&self.schema, cols)?;
+        let shape = self.shape.with_cols(schema.len());
+        let seq = self.derive(schema, &shape, move |iter| {
+            Ok(Box::new(ExtendIter {
+                exprs: exprs.clone(),
+                mode,
+                row: Vec::new(),
+                error: None,
+                iter,
+            }))
+        });
+        Ok(seq.into())
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        let schema = rename_schema(&self.schema, change)?;
+        Ok(Seq {
+            schema,
+            ..self.clone()
+        }
+        .into())
     }
 }
 
 impl Seq {
-    pub fn new(schema: Schema, shape: &Shape, iter: Rc<RefCell<dyn RelIter>>) -> Self {
+    /// A Seq that can be read many times: `make` return a new iterator from
+    /// the first row on each call
+    pub fn new<F>(schema: Schema, shape: &Shape, make: F) -> Self
+    where
+        F: Fn() -> ResultT<Box<dyn RelIter>> + 'static,
+    {
         Seq {
-            iter,
             schema,
             shape: *shape,
+            single_pass: false,
+            make: Rc::new(make),
+        }
+    }
+
+    /// A Seq that can be read only once, like a file or a socket. Read it again
+    /// is the error StreamConsumed
+    pub fn once<T: RelIter + 'static>(schema: Schema, shape: &Shape, iter: T) -> Self {
+        let iter: RefCell<Option<Box<dyn RelIter>>> = RefCell::new(Some(Box::new(iter)));
+        let make = move || iter.borrow_mut().take().ok_or(Error::StreamConsumed);
+        Seq {
+            single_pass: true,
+            ..Self::new(schema, shape, make)
+        }
+    }
+
+    /// The rows of the relation, shared by all the iterations
+    pub fn of_rows<T>(schema: Schema, shape: &Shape, of: T) -> Self
+    where
+        T: 'static,
+        RowsIter<T>: RelIter,
+    {
+        let of = Rc::new(of);
+        Self::new(schema, shape, move || {
+            Ok(Box::new(RowsIter::shared(of.clone())))
+        })
+    }
+
+    /// A Seq of rows not known until consumed, like a file or a socket
+    pub fn of_stream<T: RelIter + 'static>(schema: &Schema, iter: T) -> Self {
+        Self::once(schema.clone(), &Shape::Stream(schema.len()), iter)
+    }
+
+    pub fn of_union(schema: &Schema, shape: &Shape, lhs: &Seq, rhs: &Seq) -> Self {
+        let (lhs, rhs) = (lhs.clone(), rhs.clone());
+        let single_pass = lhs.single_pass || rhs.single_pass;
+        let make = move || {
+            let iter = UnionIter {
+                lhs: lhs.iter()?,
+                rhs: rhs.iter()?,
+                first: true,
+            };
+            Ok(Box::new(iter) as Box<dyn RelIter>)
+        };
+        Seq {
+            single_pass,
+            ..Self::new(schema.clone(), shape, make)
         }
     }
 
-    pub fn of_union(
-        schema: &Schema,
-        shape: &Shape,
-        lhs: Rc<RefCell<dyn RelIter>>,
-        rhs: Rc<RefCell<dyn RelIter>>,
-    ) -> Self {
-        let iter = UnionIter {
-            lhs,
-            rhs,
-            first: true,
+    /// The rows of a std Iterator, read only once. With exact bounds the shape
+    /// is a Vector or Table, else a Stream
+    pub fn of_iter<I>(schema: &Schema, iter: I) -> Self
+    where
+        I: Iterator<Item = Col> + 'static,
+    {
+        let cols = schema.len();
+        let shape = match iter.size_hint() {
+            (lo, Some(hi)) if lo == hi && cols == 1 => Shape::Vector(hi),
+            (lo, Some(hi)) if lo == hi => Shape::Table(cols, hi),
+            _ => Shape::Stream(cols),
         };
-        Self::new(schema.clone(), shape, ref_cell(iter))
+        Self::once(schema.clone(), &shape, IterRel::new(cols, iter))
+    }
+
+    /// A new iterator from the first row, or StreamConsumed if is single pass
+    /// and was already read
+    pub fn iter(&self) -> ResultT<Box<dyn RelIter>> {
+        (self.make)()
+    }
+
+    /// A new iterator as a std Iterator, bounded by the shape
+    pub fn iter_rows(&self) -> ResultT<IntoRows<Box<dyn RelIter>>> {
+        let (_, upper) = self.shape.size_hint();
+        Ok(IntoRows {
+            upper,
+            ..self.iter()?.into_rows()
+        })
     }
 
-    pub fn materialize(&mut self) -> Rel {
-        let mut b = self.iter.borrow_mut();
+    /// A Seq with the iterator of `make` over a new one of this
+    fn derive<F>(&self, schema: Schema, shape: &Shape, make: F) -> Self
+    where
+        F: Fn(Box<dyn RelIter>) -> ResultT<Box<dyn RelIter>> + 'static,
+    {
+        let of = self.clone();
+        Seq {
+            single_pass: self.single_pass,
+            ..Self::new(schema, shape, move || make(of.iter()?))
+        }
+    }
 
-        match self.shape {
-            Shape::Vector(rows) => {
-                let mut rows = Vec::with_capacity(rows);
-                while let Some(row) = b.next() {
-                    rows.push(row[0].clone());
-                }
+    /// The other as a Seq with the columns in the same order of this
+    fn aligned(&self, other: &Rel) -> ResultT<Seq> {
+        let pos = align_positions(&self.schema, &other.schema())?;
+        let cols: Vec<_> = pos.into_iter().map(ColumnName::Pos).collect();
+        match other.as_seq().project(&cols)? {
+            Rel::Seq(x) => Ok(x),
+            _ => unreachable!(),
+        }
+    }
 
-                Vector::new(self.schema.clone(), rows).into()
+    /// Diff or intersection, reading all of the other at the first advance
+    fn of_set(&self, other: &Rel, found: bool) -> ResultT<Self> {
+        let other = self.aligned(other)?;
+        let single_pass = self.single_pass || other.single_pass;
+        let seq = self.derive(self.schema.clone(), &self.shape, move |iter| {
+            Ok(Box::new(SetIter {
+                found,
+                other: other.iter()?,
+                set: None,
+                seen: HashSet::new(),
+                iter,
+            }))
+        });
+        Ok(Seq { single_pass, ..seq })
+    }
+
+    /// Pull all the rows into the relation of the shape. The rows of the shape
+    /// are only a hint, because a filter or a stream can give others: a Scalar or
+    /// Row that not end with a single row become a Vector or Table
+    pub fn materialize(&self) -> ResultT<Rel> {
+        let (_, hint) = self.shape.size();
+        let mut rows = Vec::with_capacity(hint);
+        let mut iter = self.iter()?;
+        while let Some(row) = iter.next() {
+            rows.push(row);
+        }
+        if let Some(e) = iter.error() {
+            return Err(e);
+        }
+        let schema = self.schema.clone();
+
+        let rel = match self.shape {
+            Shape::Scalar if rows.len() == 1 => Rel::One(rows.remove(0).swap_remove(0)),
+            Shape::Row(_) if rows.len() == 1 => Row::new(schema, rows.remove(0)).into(),
+            Shape::KV(_) => BTree::from_rows(schema, rows)?.into(),
+            Shape::Scalar | Shape::Vector(_) => Vector::from_rows(schema, rows).into(),
+            Shape::Stream(_) if schema.len() == 1 => Vector::from_rows(schema, rows).into(),
+            Shape::Row(_) | Shape::Table(_, _) | Shape::Stream(_) => {
+                Table::new(schema, rows).into()
             }
-            _ => unimplemented!(),
+        };
+        Ok(rel)
+    }
+}
+
+/// A RelIter as a std Iterator. The bounds are the ones of the RelIter, capped
+/// by the rows of the shape if came from a Seq
+pub struct IntoRows<I> {
+    iter: I,
+    upper: Option<usize>,
+}
+
+impl<I: RelIter> IntoRows<I> {
+    pub fn new(iter: I) -> Self {
+        IntoRows { iter, upper: None }
+    }
+
+    /// Why the rows ended early, if they did
+    pub fn error(&self) -> Option<Error> {
+        self.iter.error()
+    }
+}
+
+impl<I: RelIter> Iterator for IntoRows<I> {
+    type Item = Col;
+
+    fn next(&mut self) -> Option<Col> {
+        let row = self.iter.next()?;
+        self.upper = self.upper.map(|x| x.saturating_sub(1));
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lo, hi) = self.iter.size_hint();
+        match (hi, self.upper) {
+            (Some(a), Some(b)) => (lo.min(b), Some(a.min(b))),
+            (None, Some(b)) => (lo.min(b), Some(b)),
+            (hi, None) => (lo, hi),
+        }
+    }
+}
+
+/// A std Iterator of rows as a RelIter. A row that is not of `width` columns
+/// stop it, with ArityMismatch as the error()
+pub struct IterRel<I> {
+    iter: I,
+    width: usize,
+    row: Col,
+    pos: usize,
+    error: Option<Error>,
+}
+
+impl<I: Iterator<Item = Col>> IterRel<I> {
+    pub fn new(width: usize, iter: I) -> Self {
+        IterRel {
+            iter,
+            width,
+            row: Vec::new(),
+            pos: 0,
+            error: None,
         }
     }
 }
 
+impl<I: Iterator<Item = Col>> RelIter for IterRel<I> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.error.clone()
+    }
+
+    fn advance(&mut self) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        match self.iter.next() {
+            Some(row) if row.len() != self.width => {
+                self.error = Some(Error::ArityMismatch(self.pos, self.width, row.len()));
+                false
+            }
+            Some(row) => {
+                self.row = row;
+                self.pos += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //Cloned, because the iterators over this can read the row twice
+    fn row(&mut self) -> Col {
+        self.row.clone()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+//Each side has his own iterator, so only one is borrowed at a time
 struct UnionIter {
     pub first: bool,
-    pub lhs: Rc<RefCell<dyn RelIter>>,
-    pub rhs: Rc<RefCell<dyn RelIter>>,
+    pub lhs: Box<dyn RelIter>,
+    pub rhs: Box<dyn RelIter>,
 }
 
 impl RelIter for UnionIter {
     fn pos(&self) -> usize {
         if self.first {
-            self.lhs.borrow().pos()
+            self.lhs.pos()
         } else {
-            self.rhs.borrow().pos()
+            self.rhs.pos()
         }
     }
 
-    fn advance(&mut self) -> bool {
-        let mut a = self.lhs.borrow_mut();
-        let mut b = self.rhs.borrow_mut();
+    fn error(&self) -> Option<Error> {
+        self.lhs.error().or_else(|| self.rhs.error())
+    }
 
+    fn advance(&mut self) -> bool {
         if self.first {
-            if a.advance() {
-                true
-            } else {
-                self.first = false;
-                b.advance()
+            if self.lhs.advance() {
+                return true;
             }
-        } else {
-            b.advance()
+            self.first = false;
         }
+        self.rhs.advance()
     }
 
     fn row(&mut self) -> Col {
         if self.first {
-            let mut a = self.lhs.borrow_mut();
-            a.row()
+            self.lhs.row()
         } else {
-            let mut b = self.rhs.borrow_mut();
-            b.row()
+            self.rhs.row()
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, x) = self.rhs.size_hint();
+        if !self.first {
+            return (a, x);
+        }
+        let (b, y) = self.lhs.size_hint();
+        (
+            a.saturating_add(b),
+            x.and_then(|x| y.and_then(|y| x.checked_add(y))),
+        )
+    }
+}
+
+/// Sort on demand: the rows are pulled from the source at the first advance
+struct SortIter {
+    pub keys: Vec<(usize, SortKey)>,
+    pub iter: Box<dyn RelIter>,
+    pub rows: Option<Vec<Col>>,
+    pub pos: usize,
+}
+
+impl RelIter for SortIter {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.iter.error()
+    }
+
+    fn advance(&mut self) -> bool {
+        if self.rows.is_none() {
+            //On a error of the source there are no rows, see error()
+            let mut rows = self.iter.rows_left().unwrap_or_default();
+            sort_rows(&self.keys, &mut rows);
+            self.rows = Some(rows);
+        }
+
+        let ok = self.pos < self.rows.as_ref().map_or(0, |x| x.len());
+        self.pos += 1;
+        ok
+    }
+
+    fn row(&mut self) -> Col {
+        let rows = self.rows.as_ref().unwrap();
+        rows[self.pos - 1].clone()
+    }
+}
+
+/// Return the rows (without duplicates) that are found or not in the other
+struct SetIter {
+    pub found: bool,
+    pub other: Box<dyn RelIter>,
+    pub set: Option<HashSet<Col>>,
+    pub seen: HashSet<Col>,
+    pub iter: Box<dyn RelIter>,
+}
+
+impl RelIter for SetIter {
+    fn pos(&self) -> usize {
+        self.iter.pos()
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.iter.error().or_else(|| self.other.error())
+    }
+
+    fn advance(&mut self) -> bool {
+        let other = &mut self.other;
+        let set = self.set.get_or_insert_with(|| {
+            let mut set = HashSet::new();
+            while let Some(row) = other.next() {
+                set.insert(row);
+            }
+            set
+        });
+        if other.error().is_some() {
+            return false;
+        }
+
+        let iter = &mut self.iter;
+        while iter.advance() {
+            let row = iter.row();
+            if set.contains(&row) == self.found && self.seen.insert(row) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn row(&mut self) -> Col {
+        self.iter.row()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Stop pulling from the source as soon as `take` rows are returned
+struct LimitIter {
+    pub skip: usize,
+    pub take: usize,
+    pub iter: Box<dyn RelIter>,
+}
+
+impl RelIter for LimitIter {
+    fn pos(&self) -> usize {
+        self.iter.pos()
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.iter.error()
+    }
+
+    fn advance(&mut self) -> bool {
+        let iter = &mut self.iter;
+        while self.skip > 0 {
+            self.skip -= 1;
+            if !iter.advance() {
+                self.take = 0;
+                return false;
+            }
+        }
+
+        if self.take > 0 && iter.advance() {
+            self.take -= 1;
+            true
+        } else {
+            self.take = 0;
+            false
+        }
+    }
+
+    fn row(&mut self) -> Col {
+        self.iter.row()
+    }
+}
+
+/// Skip the rows with a key already seen
+struct DistinctIter {
+    pub pos: Pos,
+    pub seen: HashSet<Col>,
+    pub iter: Box<dyn RelIter>,
+}
+
+impl RelIter for DistinctIter {
+    fn pos(&self) -> usize {
+        self.iter.pos()
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.iter.error()
+    }
+
+    fn advance(&mut self) -> bool {
+        let iter = &mut self.iter;
+        while iter.advance() {
+            let key = project_row(&self.pos, &iter.row());
+            if self.seen.insert(key) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn row(&mut self) -> Col {
+        self.iter.row()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+struct ProjectIter {
+    pub pos: Pos,
+    pub iter: Box<dyn RelIter>,
+}
+
+impl RelIter for ProjectIter {
+    fn pos(&self) -> usize {
+        self.iter.pos()
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.iter.error()
+    }
+
+    fn advance(&mut self) -> bool {
+        self.iter.advance()
+    }
+
+    fn row(&mut self) -> Col {
+        project_row(&self.pos, &self.iter.row())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Add the computed columns. The first error stop the rows, and is kept for
+/// error()
+struct ExtendIter {
+    pub exprs: Vec<RowExpr>,
+    pub mode: OnError,
+    pub row: Col,
+    pub error: Option<Error>,
+    pub iter: Box<dyn RelIter>,
+}
+
+impl RelIter for ExtendIter {
+    fn pos(&self) -> usize {
+        self.iter.pos()
+    }
+
+    fn error(&self) -> Option<Error> {
+        self.error.clone().or_else(|| self.iter.error())
+    }
+
+    fn advance(&mut self) -> bool {
+        if self.error.is_some() || !self.iter.advance() {
+            return false;
+        }
+        match extend_row(&self.exprs, self.iter.row(), self.mode) {
+            Ok(row) => {
+                self.row = row;
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+
+    fn row(&mut self) -> Col {
+        self.row.clone()
+    }
+
+    //Can stop early on a error
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
 }
 
 struct FilterIter {
-    pub cmp: CmOp,
-    pub iter: Rc<dyn RelIter>,
+    pub pred: Predicate,
+    pub iter: Box<dyn RelIter>,
 }
 
 impl RelIter for FilterIter {
@@ -135,13 +688,15 @@ impl RelIter for FilterIter {
         self.iter.pos()
     }
 
+    fn error(&self) -> Option<Error> {
+        self.iter.error()
+    }
+
     fn advance(&mut self) -> bool {
-        let iter = std::rc::Rc::get_mut(&mut self.iter).unwrap();
-        let apply = self.cmp.get_fn();
+        let iter = &mut self.iter;
 
         while iter.advance() {
-            let value = &iter.row()[self.cmp.lhs];
-            if apply(value, &self.cmp.rhs) {
+            if self.pred.eval(&iter.row()) {
                 return true;
             }
         }
@@ -149,8 +704,12 @@ impl RelIter for FilterIter {
     }
 
     fn row(&mut self) -> Col {
-        let iter = std::rc::Rc::get_mut(&mut self.iter).unwrap();
-        iter.row()
+        self.iter.row()
+    }
+
+    //Can drop any of the rows left, so only the upper bound is known
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
     }
 }
 
@@ -184,20 +743,14 @@ impl Eq for Seq {}
 
 impl PartialOrd for Seq {
     fn partial_cmp(&self, other: &Seq) -> Option<Ordering> {
-        let a = self.shape.partial_cmp(&other.shape);
-        let b = self.schema.partial_cmp(&other.schema);
-        a.partial_cmp(&b)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Seq {
     fn cmp(&self, other: &Seq) -> Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-
-impl Clone for Seq {
-    fn clone(&self) -> Self {
-        Seq::new(self.schema.clone(), &self.shape, self.iter.clone())
+        self.shape
+            .cmp(&other.shape)
+            .then_with(|| self.schema.cmp(&other.schema))
     }
 }
@@ -2,6 +2,8 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 
+use crate::dsl::*;
+use crate::error::*;
 use crate::types::*;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -15,6 +17,7 @@ pub struct IoFile {
     pub file: BufReader<File>,
     pos: usize,
     line: Option<Scalar>,
+    error: Option<Error>,
 }
 
 impl IoFile {
@@ -24,8 +27,15 @@ impl IoFile {
             file,
             line: None,
             pos: 0,
+            error: None,
         }
     }
+
+    /// The lines, not counted until read
+    pub fn into_seq(self) -> Seq {
+        let schema = schema_single("line", DataType::UTF8);
+        Seq::of_stream(&schema, self)
+    }
 }
 
 impl RelIter for IoFile {
@@ -33,7 +43,14 @@ impl RelIter for IoFile {
         self.pos
     }
 
+    fn error(&self) -> Option<Error> {
+        self.error.clone()
+    }
+
     fn advance(&mut self) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
         let mut buf = String::new();
         match self.file.read_line(&mut buf) {
             Ok(0) => {
@@ -50,7 +67,12 @@ impl RelIter for IoFile {
                 self.line = Some(buf.into());
                 true
             }
-            Err(e) => false,
+            //Not the end of the lines, so it is kept for error()
+            Err(e) => {
+                self.line = None;
+                self.error = Some(Error::Io(e.to_string()));
+                false
+            }
         }
     }
 
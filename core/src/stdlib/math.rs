@@ -1,47 +1,496 @@
+use std::convert::TryFrom;
 use std::ops::*;
 
+extern crate decorum;
+use decorum::R64;
+
 extern crate rust_decimal;
-use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
+use rust_decimal::{Decimal, RoundingStrategy};
 
-use crate::scalars::bin_op;
+use crate::error::*;
+use crate::stdlib::time::{time_add, time_minus};
 use crate::types::*;
 
-pub fn math_add(x: &Scalar, y: &Scalar) -> Scalar {
-    match (x, y) {
-        (Scalar::ISize(a), Scalar::ISize(b)) => bin_op::<isize, _>(Add::add, *a, *b),
-        (Scalar::I32(a), Scalar::I32(b)) => bin_op::<i32, _>(Add::add, *a, *b),
-        (Scalar::I64(a), Scalar::I64(b)) => bin_op::<i64, _>(Add::add, *a, *b),
-        (Scalar::Decimal(a), Scalar::Decimal(b)) => bin_op::<Decimal, _>(Add::add, *a, *b),
-        (a, b) => panic!("Argument {:?} <> {:?}", a, b),
+fn is_numeric(of: &DataType) -> bool {
+    matches!(
+        of,
+        DataType::I32 | DataType::ISize | DataType::I64 | DataType::F64 | DataType::Decimal
+    )
+}
+
+/// The type both operands take before a math operation: the larger in the
+/// DataType order. Mixing Decimal & F64 give a Decimal, so the result is exact
+pub fn promote_kind(a: DataType, b: DataType) -> ResultT<DataType> {
+    if is_numeric(&a) && is_numeric(&b) {
+        Ok(std::cmp::max(a, b))
+    } else {
+        Err(Error::TypeMismatch(a, b))
+    }
+}
+
+/// Cast a number into the same or a larger numeric type
+pub fn cast_num(x: &Scalar, kind: DataType) -> ResultT<Scalar> {
+    let fail = || Error::InvalidCast(x.kind(), kind.clone());
+    if x.kind() == kind {
+        return Ok(x.clone());
     }
+    let casted = match (x, &kind) {
+        (Scalar::I32(a), DataType::ISize) => (*a as isize).into(),
+        (Scalar::I32(a), DataType::I64) => i64::from(*a).into(),
+        (Scalar::I32(a), DataType::F64) => R64::from_inner(f64::from(*a)).into(),
+        (Scalar::I32(a), DataType::Decimal) => Decimal::from(*a).into(),
+        (Scalar::ISize(a), DataType::I64) => (*a as i64).into(),
+        (Scalar::ISize(a), DataType::F64) => R64::from_inner(*a as f64).into(),
+        (Scalar::ISize(a), DataType::Decimal) => Decimal::from(*a).into(),
+        (Scalar::I64(a), DataType::F64) => R64::from_inner(*a as f64).into(),
+        (Scalar::I64(a), DataType::Decimal) => Decimal::from(*a).into(),
+        (Scalar::F64(a), DataType::Decimal) => {
+            Decimal::from_f64(a.into_inner()).ok_or_else(fail)?.into()
+        }
+        _ => return Err(fail()),
+    };
+    Ok(casted)
 }
 
-pub fn math_minus(x: &Scalar, y: &Scalar) -> Scalar {
-    match (x, y) {
-        (Scalar::ISize(a), Scalar::ISize(b)) => bin_op::<isize, _>(Sub::sub, *a, *b),
-        (Scalar::I32(a), Scalar::I32(b)) => bin_op::<i32, _>(Sub::sub, *a, *b),
-        (Scalar::I64(a), Scalar::I64(b)) => bin_op::<i64, _>(Sub::sub, *a, *b),
-        (Scalar::Decimal(a), Scalar::Decimal(b)) => bin_op::<Decimal, _>(Sub::sub, *a, *b),
-        (a, b) => panic!("Argument {:?} <> {:?}", a, b),
+/// Both operands as the same numeric type, or None if any is null. A null
+/// not hide a operand that is not a number
+pub fn promote(x: &Scalar, y: &Scalar) -> ResultT<Option<(Scalar, Scalar)>> {
+    if *x == Scalar::None || *y == Scalar::None {
+        let other = if *x == Scalar::None { y } else { x };
+        return match other.kind() {
+            DataType::None => Ok(None),
+            kind if is_numeric(&kind) => Ok(None),
+            _ => Err(Error::TypeMismatch(x.kind(), y.kind())),
+        };
     }
+    let kind = promote_kind(x.kind(), y.kind())?;
+    Ok(Some((cast_num(x, kind.clone())?, cast_num(y, kind)?)))
 }
 
-pub fn math_mul(x: &Scalar, y: &Scalar) -> Scalar {
-    match (x, y) {
-        (Scalar::ISize(a), Scalar::ISize(b)) => bin_op::<isize, _>(Mul::mul, *a, *b),
-        (Scalar::I32(a), Scalar::I32(b)) => bin_op::<i32, _>(Mul::mul, *a, *b),
-        (Scalar::I64(a), Scalar::I64(b)) => bin_op::<i64, _>(Mul::mul, *a, *b),
-        (Scalar::Decimal(a), Scalar::Decimal(b)) => bin_op::<Decimal, _>(Mul::mul, *a, *b),
-        (a, b) => panic!("Argument {:?} <> {:?}", a, b),
+fn is_zero(x: &Scalar) -> bool {
+    match x {
+        Scalar::ISize(a) => *a == 0,
+        Scalar::I32(a) => *a == 0,
+        Scalar::I64(a) => *a == 0,
+        Scalar::F64(a) => a.into_inner() == 0.0,
+        Scalar::Decimal(a) => a.is_zero(),
+        _ => false,
     }
 }
 
-pub fn math_div(x: &Scalar, y: &Scalar) -> Scalar {
-    match (x, y) {
-        (Scalar::ISize(a), Scalar::ISize(b)) => bin_op::<isize, _>(Div::div, *a, *b),
-        (Scalar::I32(a), Scalar::I32(b)) => bin_op::<i32, _>(Div::div, *a, *b),
-        (Scalar::I64(a), Scalar::I64(b)) => bin_op::<i64, _>(Div::div, *a, *b),
-        (Scalar::Decimal(a), Scalar::Decimal(b)) => bin_op::<Decimal, _>(Div::div, *a, *b),
-        (a, b) => panic!("Argument {:?} <> {:?}", a, b),
+//Approximated. For the sign of a overflow, and the operations computed as F64
+fn to_f64(x: &Scalar) -> f64 {
+    match x {
+        Scalar::ISize(a) => *a as f64,
+        Scalar::I32(a) => f64::from(*a),
+        Scalar::I64(a) => *a as f64,
+        Scalar::F64(a) => a.into_inner(),
+        Scalar::Decimal(a) => a.to_f64().unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+//None if overflow
+fn to_float(x: f64) -> ResultT<Option<Scalar>> {
+    if x.is_nan() {
+        Err(Error::Domain("the result is not a number".into()))
+    } else if x.is_finite() {
+        Ok(Some(R64::from_inner(x).into()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The min or max value of the numeric type
+fn limit(kind: &DataType, positive: bool) -> Option<Scalar> {
+    let x = match (kind, positive) {
+        (DataType::ISize, true) => isize::MAX.into(),
+        (DataType::ISize, false) => isize::MIN.into(),
+        (DataType::I32, true) => i32::MAX.into(),
+        (DataType::I32, false) => i32::MIN.into(),
+        (DataType::I64, true) => i64::MAX.into(),
+        (DataType::I64, false) => i64::MIN.into(),
+        (DataType::F64, true) => R64::from_inner(f64::MAX).into(),
+        (DataType::F64, false) => R64::from_inner(f64::MIN).into(),
+        (DataType::Decimal, true) => Decimal::max_value().into(),
+        (DataType::Decimal, false) => Decimal::min_value().into(),
+        _ => return None,
+    };
+    Some(x)
+}
+
+fn on_error(mode: OnError, error: Error, saturated: Option<Scalar>) -> ResultT<Scalar> {
+    match (mode, saturated) {
+        (OnError::Null, _) => Ok(Scalar::None),
+        (OnError::Saturate, Some(x)) => Ok(x),
+        _ => Err(error),
+    }
+}
+
+fn any_value(_x: &Scalar, _y: &Scalar) -> ResultT<()> {
+    Ok(())
+}
+
+fn not_zero(_x: &Scalar, y: &Scalar) -> ResultT<()> {
+    if is_zero(y) {
+        Err(Error::DivideByZero)
+    } else {
+        Ok(())
+    }
+}
+
+//Integers only have positive exponents, decimals only integer ones and a
+//negative one is a division by the base
+fn exponent(x: &Scalar, y: &Scalar) -> ResultT<()> {
+    match y {
+        Scalar::ISize(_) | Scalar::I32(_) | Scalar::I64(_) if to_f64(y) < 0.0 => {
+            Err(Error::Domain("negative exponent of a integer".into()))
+        }
+        Scalar::Decimal(b) if !b.fract().is_zero() => {
+            Err(Error::Domain("fractional exponent of a decimal".into()))
+        }
+        Scalar::Decimal(b) if b.is_sign_negative() => not_zero(y, x),
+        _ => Ok(()),
+    }
+}
+
+fn div_trunc(x: f64, y: f64) -> f64 {
+    (x / y).trunc()
+}
+
+/// The checked operations missing in the integers & Decimal, None on overflow
+trait Checked: Sized {
+    fn checked_int_div(self, other: Self) -> Option<Self>;
+    fn checked_modulo(self, other: Self) -> Option<Self>;
+    fn checked_power(self, exp: Self) -> Option<Self>;
+}
+
+/// Bitwise operations, None if the shift is out of range
+trait Bits: Sized {
+    fn checked_and(self, other: Self) -> Option<Self>;
+    fn checked_or(self, other: Self) -> Option<Self>;
+    fn checked_xor(self, other: Self) -> Option<Self>;
+    fn checked_shift_left(self, other: Self) -> Option<Self>;
+    fn checked_shift_right(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! checked_int {
+    ($kind:ty) => {
+        impl Checked for $kind {
+            fn checked_int_div(self, other: Self) -> Option<Self> {
+                self.checked_div(other)
+            }
+
+            //Only MIN % -1 overflow, and is 0
+            fn checked_modulo(self, other: Self) -> Option<Self> {
+                Some(self.wrapping_rem(other))
+            }
+
+            fn checked_power(self, exp: Self) -> Option<Self> {
+                u32::try_from(exp).ok().and_then(|x| self.checked_pow(x))
+            }
+        }
+
+        impl Bits for $kind {
+            fn checked_and(self, other: Self) -> Option<Self> {
+                Some(self & other)
+            }
+
+            fn checked_or(self, other: Self) -> Option<Self> {
+                Some(self | other)
+            }
+
+            fn checked_xor(self, other: Self) -> Option<Self> {
+                Some(self ^ other)
+            }
+
+            fn checked_shift_left(self, other: Self) -> Option<Self> {
+                u32::try_from(other).ok().and_then(|x| self.checked_shl(x))
+            }
+
+            fn checked_shift_right(self, other: Self) -> Option<Self> {
+                u32::try_from(other).ok().and_then(|x| self.checked_shr(x))
+            }
+        }
+    };
+}
+
+checked_int!(isize);
+checked_int!(i32);
+checked_int!(i64);
+
+impl Checked for Decimal {
+    fn checked_int_div(self, other: Self) -> Option<Self> {
+        self.checked_div(other).map(|x| x.trunc())
+    }
+
+    fn checked_modulo(self, other: Self) -> Option<Self> {
+        self.checked_rem(other)
+    }
+
+    //By squaring, the exponent is a integer
+    fn checked_power(self, exp: Self) -> Option<Self> {
+        let one = Decimal::new(1, 0);
+        let mut times = exp.abs().to_u64()?;
+        let mut base = self;
+        let mut total = one;
+        while times > 0 {
+            if times & 1 == 1 {
+                total = total.checked_mul(base)?;
+            }
+            times >>= 1;
+            if times > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        if exp.is_sign_negative() {
+            one.checked_div(total)
+        } else {
+            Some(total)
+        }
+    }
+}
+
+fn is_temporal(of: &Scalar) -> bool {
+    matches!(
+        of.kind(),
+        DataType::Date | DataType::Time | DataType::DateTime | DataType::Duration
+    )
+}
+
+fn no_temporal(x: &Scalar, y: &Scalar) -> ResultT<Option<Scalar>> {
+    Err(Error::TypeMismatch(x.kind(), y.kind()))
+}
+
+/// Define the checked operation `$with`, and `$name` that fail on overflow.
+/// Before, `$guard` check the operands are in the domain of the operation.
+/// Dates & durations go to `$temporal`
+macro_rules! math_op {
+    ($name:ident, $with:ident, $checked:ident, $float:path, $guard:path, $temporal:path) => {
+        pub fn $name(x: &Scalar, y: &Scalar) -> ResultT<Scalar> {
+            $with(x, y, OnError::Fail)
+        }
+
+        pub fn $with(x: &Scalar, y: &Scalar, mode: OnError) -> ResultT<Scalar> {
+            if is_temporal(x) || is_temporal(y) {
+                return match $temporal(x, y)? {
+                    Some(x) => Ok(x),
+                    None => on_error(mode, Error::Overflow(x.kind()), None),
+                };
+            }
+            let (x, y) = match promote(x, y)? {
+                Some(pair) => pair,
+                None => return Ok(Scalar::None),
+            };
+            if let Err(e) = $guard(&x, &y) {
+                return on_error(mode, e, None);
+            }
+            let result = match (&x, &y) {
+                (Scalar::ISize(a), Scalar::ISize(b)) => Ok(a.$checked(*b).map(Scalar::from)),
+                (Scalar::I32(a), Scalar::I32(b)) => Ok(a.$checked(*b).map(Scalar::from)),
+                (Scalar::I64(a), Scalar::I64(b)) => Ok(a.$checked(*b).map(Scalar::from)),
+                (Scalar::Decimal(a), Scalar::Decimal(b)) => Ok(a.$checked(*b).map(Scalar::from)),
+                (Scalar::F64(a), Scalar::F64(b)) => {
+                    to_float($float(a.into_inner(), b.into_inner()))
+                }
+                (a, b) => return Err(Error::TypeMismatch(a.kind(), b.kind())),
+            };
+            match result {
+                Ok(Some(x)) => Ok(x),
+                Ok(None) => {
+                    let kind = x.kind();
+                    let positive = $float(to_f64(&x), to_f64(&y)) >= 0.0;
+                    on_error(mode, Error::Overflow(kind.clone()), limit(&kind, positive))
+                }
+                Err(e) => on_error(mode, e, None),
+            }
+        }
+    };
+}
+
+math_op!(
+    math_add,
+    math_add_with,
+    checked_add,
+    Add::add,
+    any_value,
+    time_add
+);
+math_op!(
+    math_minus,
+    math_minus_with,
+    checked_sub,
+    Sub::sub,
+    any_value,
+    time_minus
+);
+math_op!(
+    math_mul,
+    math_mul_with,
+    checked_mul,
+    Mul::mul,
+    any_value,
+    no_temporal
+);
+math_op!(
+    math_div,
+    math_div_with,
+    checked_div,
+    Div::div,
+    not_zero,
+    no_temporal
+);
+math_op!(
+    math_mod,
+    math_mod_with,
+    checked_modulo,
+    Rem::rem,
+    not_zero,
+    no_temporal
+);
+math_op!(
+    math_int_div,
+    math_int_div_with,
+    checked_int_div,
+    div_trunc,
+    not_zero,
+    no_temporal
+);
+math_op!(
+    math_pow,
+    math_pow_with,
+    checked_power,
+    f64::powf,
+    exponent,
+    no_temporal
+);
+
+/// Like math_op!, only for integers
+macro_rules! bit_op {
+    ($name:ident, $with:ident, $checked:ident, $label:expr) => {
+        pub fn $name(x: &Scalar, y: &Scalar) -> ResultT<Scalar> {
+            $with(x, y, OnError::Fail)
+        }
+
+        pub fn $with(x: &Scalar, y: &Scalar, mode: OnError) -> ResultT<Scalar> {
+            let (x, y) = match promote(x, y)? {
+                Some(pair) => pair,
+                None => return Ok(Scalar::None),
+            };
+            let result = match (&x, &y) {
+                (Scalar::ISize(a), Scalar::ISize(b)) => a.$checked(*b).map(Scalar::from),
+                (Scalar::I32(a), Scalar::I32(b)) => a.$checked(*b).map(Scalar::from),
+                (Scalar::I64(a), Scalar::I64(b)) => a.$checked(*b).map(Scalar::from),
+                (a, _) => return Err(Error::Unsupported($label.into(), a.kind())),
+            };
+            match result {
+                Some(x) => Ok(x),
+                None => on_error(mode, Error::Overflow(x.kind()), None),
+            }
+        }
+    };
+}
+
+bit_op!(math_bit_and, math_bit_and_with, checked_and, "bitwise and");
+bit_op!(math_bit_or, math_bit_or_with, checked_or, "bitwise or");
+bit_op!(math_bit_xor, math_bit_xor_with, checked_xor, "bitwise xor");
+bit_op!(math_shl, math_shl_with, checked_shift_left, "shift left");
+bit_op!(math_shr, math_shr_with, checked_shift_right, "shift right");
+
+fn negate(x: &Scalar) -> ResultT<Option<Scalar>> {
+    Ok(match x {
+        Scalar::ISize(a) => a.checked_neg().map(Scalar::from),
+        Scalar::I32(a) => a.checked_neg().map(Scalar::from),
+        Scalar::I64(a) => a.checked_neg().map(Scalar::from),
+        Scalar::Decimal(a) => Some((-*a).into()),
+        Scalar::F64(a) => Some((-*a).into()),
+        _ => None,
+    })
+}
+
+fn absolute(x: &Scalar) -> ResultT<Option<Scalar>> {
+    Ok(match x {
+        Scalar::ISize(a) => a.checked_abs().map(Scalar::from),
+        Scalar::I32(a) => a.checked_abs().map(Scalar::from),
+        Scalar::I64(a) => a.checked_abs().map(Scalar::from),
+        Scalar::Decimal(a) => Some(a.abs().into()),
+        Scalar::F64(a) => Some(R64::from_inner(a.into_inner().abs()).into()),
+        _ => None,
+    })
+}
+
+/// Round with `dec` or `float` at the decimal places, moving the point. The
+/// integers & the numbers without more decimals are returned as is
+fn round_at(
+    x: &Scalar,
+    places: u32,
+    dec: fn(&Decimal) -> Decimal,
+    float: fn(f64) -> f64,
+) -> Scalar {
+    match x {
+        Scalar::Decimal(a) if a.scale() > places => {
+            let unit = Decimal::new(1, places);
+            let moved = a.checked_div(unit).map(|x| dec(&x));
+            match moved.and_then(|x| x.checked_mul(unit)) {
+                Some(x) => x.into(),
+                None => x.clone(),
+            }
+        }
+        Scalar::F64(a) => {
+            let unit = 10f64.powi(places.min(i32::MAX as u32) as i32);
+            let moved = a.into_inner() * unit;
+            if moved.is_finite() && unit.is_finite() {
+                R64::from_inner(float(moved) / unit).into()
+            } else {
+                x.clone()
+            }
+        }
+        x => x.clone(),
+    }
+}
+
+fn round_half_up(x: &Decimal) -> Decimal {
+    x.round_dp_with_strategy(0, RoundingStrategy::RoundHalfUp)
+}
+
+fn real(op: UnaryOp, x: &Scalar) -> ResultT<Option<Scalar>> {
+    //A decimal not cast to F64, because the cast only go to a larger type
+    let x = to_f64(x);
+    let result = match op {
+        UnaryOp::Sqrt if x < 0.0 => return Err(Error::Domain("sqrt of a negative".into())),
+        UnaryOp::Log if x <= 0.0 => return Err(Error::Domain("log of zero or negative".into())),
+        UnaryOp::Sqrt => x.sqrt(),
+        UnaryOp::Log => x.ln(),
+        _ => x.exp(),
+    };
+    to_float(result)
+}
+
+/// The unary operations on numbers. The overflows are only on the positive side
+pub fn math_unary(op: UnaryOp, x: &Scalar, mode: OnError) -> ResultT<Scalar> {
+    let kind = x.kind();
+    if kind == DataType::None {
+        return Ok(Scalar::None);
+    }
+    if !is_numeric(&kind) {
+        return Err(Error::Unsupported(format!("{:?}", op), kind));
+    }
+    let result = match op {
+        UnaryOp::Neg => negate(x),
+        UnaryOp::Abs => absolute(x),
+        UnaryOp::Round(places) => Ok(Some(round_at(x, places, round_half_up, f64::round))),
+        UnaryOp::Floor(places) => Ok(Some(round_at(x, places, Decimal::floor, f64::floor))),
+        UnaryOp::Ceil(places) => Ok(Some(round_at(x, places, Decimal::ceil, f64::ceil))),
+        UnaryOp::Trunc(places) => Ok(Some(round_at(x, places, Decimal::trunc, f64::trunc))),
+        UnaryOp::Sqrt | UnaryOp::Log | UnaryOp::Exp => real(op, x),
+    };
+    match result {
+        Ok(Some(x)) => Ok(x),
+        Ok(None) => {
+            let kind = op.kind(kind);
+            on_error(mode, Error::Overflow(kind.clone()), limit(&kind, true))
+        }
+        Err(e) => on_error(mode, e, None),
     }
 }
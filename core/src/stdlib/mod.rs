@@ -1,2 +1,23 @@
 pub mod file;
 pub mod math;
+pub mod text;
+pub mod time;
+
+use crate::error::ResultT;
+use crate::types::Scalar;
+
+pub type NativeExpr = fn(&[Scalar]) -> ResultT<Scalar>;
+
+/// A function implemented in rust, with the names of his params in order
+#[derive(Debug, Clone, Copy)]
+pub struct NativeFun {
+    pub name: &'static str,
+    pub params: &'static [&'static str],
+    pub fun: NativeExpr,
+}
+
+impl NativeFun {
+    pub fn new(name: &'static str, params: &'static [&'static str], fun: NativeExpr) -> Self {
+        NativeFun { name, params, fun }
+    }
+}
@@ -0,0 +1,225 @@
+use std::rc::Rc;
+
+use crate::dsl::schema_it;
+use crate::error::*;
+use crate::relational::to_rows;
+use crate::stdlib::math::cast_num;
+use crate::stdlib::NativeFun;
+use crate::types::*;
+
+//Like in SQL, a null argument give null
+pub(crate) fn any_null(of: &[&Scalar]) -> bool {
+    of.iter().any(|x| **x == Scalar::None)
+}
+
+pub(crate) fn text(of: &Scalar) -> ResultT<&str> {
+    match of {
+        Scalar::UTF8(x) => Ok(x),
+        x => Err(Error::InvalidCast(x.kind(), DataType::UTF8)),
+    }
+}
+
+//Positions & sizes are in chars, never negative
+fn count(of: &Scalar) -> ResultT<usize> {
+    match cast_num(of, DataType::I64)? {
+        Scalar::I64(x) if x >= 0 => Ok(x as usize),
+        _ => Err(Error::Domain(format!("{} is not a position", of))),
+    }
+}
+
+/// Apply to the text, a null stay null
+fn map_text<F>(of: &Scalar, apply: F) -> ResultT<Scalar>
+where
+    F: FnOnce(&str) -> Scalar,
+{
+    if any_null(&[of]) {
+        return Ok(Scalar::None);
+    }
+    Ok(apply(text(of)?))
+}
+
+fn map_text2<F>(of: &Scalar, other: &Scalar, apply: F) -> ResultT<Scalar>
+where
+    F: FnOnce(&str, &str) -> Scalar,
+{
+    if any_null(&[of, other]) {
+        return Ok(Scalar::None);
+    }
+    Ok(apply(text(of)?, text(other)?))
+}
+
+/// The length in chars
+pub fn text_len(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| (x.chars().count() as i64).into())
+}
+
+/// The length in bytes of the UTF8 encoding
+pub fn text_len_bytes(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| (x.len() as i64).into())
+}
+
+pub fn text_upper(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| x.to_uppercase().into())
+}
+
+pub fn text_lower(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| x.to_lowercase().into())
+}
+
+/// For caseless comparisons. Going to upper first fold the chars without a
+/// single lowercase, like "ß" => "ss" or the final sigma
+pub fn text_fold(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| x.to_uppercase().to_lowercase().into())
+}
+
+pub fn text_trim(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| x.trim().into())
+}
+
+pub fn text_trim_start(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| x.trim_start().into())
+}
+
+pub fn text_trim_end(of: &Scalar) -> ResultT<Scalar> {
+    map_text(of, |x| x.trim_end().into())
+}
+
+/// Up to `len` chars from the char at `start`, counting from 0
+pub fn text_substring(of: &Scalar, start: &Scalar, len: &Scalar) -> ResultT<Scalar> {
+    if any_null(&[of, start, len]) {
+        return Ok(Scalar::None);
+    }
+    let (start, len) = (count(start)?, count(len)?);
+    let part: String = text(of)?.chars().skip(start).take(len).collect();
+    Ok(part.into())
+}
+
+/// The char position of the first match, or null if not found
+pub fn text_find(of: &Scalar, pattern: &Scalar) -> ResultT<Scalar> {
+    map_text2(of, pattern, |x, pattern| match x.find(pattern) {
+        Some(pos) => (x[..pos].chars().count() as i64).into(),
+        None => Scalar::None,
+    })
+}
+
+pub fn text_replace(of: &Scalar, from: &Scalar, to: &Scalar) -> ResultT<Scalar> {
+    if any_null(&[of, from, to]) {
+        return Ok(Scalar::None);
+    }
+    Ok(text(of)?.replace(text(from)?, text(to)?).into())
+}
+
+pub fn text_starts_with(of: &Scalar, prefix: &Scalar) -> ResultT<Scalar> {
+    map_text2(of, prefix, |x, prefix| x.starts_with(prefix).into())
+}
+
+pub fn text_ends_with(of: &Scalar, suffix: &Scalar) -> ResultT<Scalar> {
+    map_text2(of, suffix, |x, suffix| x.ends_with(suffix).into())
+}
+
+pub fn text_concat(of: &Scalar, other: &Scalar) -> ResultT<Scalar> {
+    map_text2(of, other, |x, other| [x, other].concat().into())
+}
+
+/// The parts between the separator, as a vector. With a null text or
+/// separator, is a vector with a single null
+pub fn text_split(of: &Scalar, sep: &Scalar) -> ResultT<Vector> {
+    let schema = schema_it(DataType::UTF8);
+    if any_null(&[of, sep]) {
+        return Ok(Vector::new(schema, vec![Scalar::None]));
+    }
+    let data = text(of)?.split(text(sep)?).map(Scalar::from);
+    Ok(Vector::new(schema, data.collect()))
+}
+
+/// Join the texts with the separator, skipping the nulls
+pub fn text_join(of: &[Scalar], sep: &Scalar) -> ResultT<Scalar> {
+    if any_null(&[sep]) {
+        return Ok(Scalar::None);
+    }
+    let parts = of
+        .iter()
+        .filter(|x| **x != Scalar::None)
+        .map(text)
+        .collect::<ResultT<Vec<_>>>()?;
+    Ok(parts.join(text(sep)?).into())
+}
+
+fn pad(of: &Scalar, width: &Scalar, fill: &Scalar, left: bool) -> ResultT<Scalar> {
+    if any_null(&[of, width, fill]) {
+        return Ok(Scalar::None);
+    }
+    let (x, width, fill) = (text(of)?, count(width)?, text(fill)?);
+    if fill.is_empty() {
+        return Err(Error::Domain("pad with a empty text".into()));
+    }
+    let missing = width.saturating_sub(x.chars().count());
+    let padding: String = fill.chars().cycle().take(missing).collect();
+    if left {
+        Ok([padding.as_str(), x].concat().into())
+    } else {
+        Ok([x, padding.as_str()].concat().into())
+    }
+}
+
+/// Fill at the left, repeating the fill, until is `width` chars
+pub fn text_pad_left(of: &Scalar, width: &Scalar, fill: &Scalar) -> ResultT<Scalar> {
+    pad(of, width, fill, true)
+}
+
+/// Fill at the right, repeating the fill, until is `width` chars
+pub fn text_pad_right(of: &Scalar, width: &Scalar, fill: &Scalar) -> ResultT<Scalar> {
+    pad(of, width, fill, false)
+}
+
+//The relations in the interpreter are scalars
+fn split_rel(of: &[Scalar]) -> ResultT<Scalar> {
+    let rel: Rel = text_split(&of[0], &of[1])?.into();
+    Ok(Scalar::Rel(Rc::new(rel)))
+}
+
+fn join_rel(of: &[Scalar]) -> ResultT<Scalar> {
+    match &of[0] {
+        Scalar::Rel(rel) => {
+            let parts: Vec<_> = to_rows(rel.as_ref())?.into_iter().flatten().collect();
+            text_join(&parts, &of[1])
+        }
+        x => text_join(std::slice::from_ref(x), &of[1]),
+    }
+}
+
+/// The functions for the interpreter
+pub fn functions() -> Vec<NativeFun> {
+    vec![
+        NativeFun::new("len", &["text"], |x| text_len(&x[0])),
+        NativeFun::new("len_bytes", &["text"], |x| text_len_bytes(&x[0])),
+        NativeFun::new("upper", &["text"], |x| text_upper(&x[0])),
+        NativeFun::new("lower", &["text"], |x| text_lower(&x[0])),
+        NativeFun::new("fold", &["text"], |x| text_fold(&x[0])),
+        NativeFun::new("trim", &["text"], |x| text_trim(&x[0])),
+        NativeFun::new("trim_start", &["text"], |x| text_trim_start(&x[0])),
+        NativeFun::new("trim_end", &["text"], |x| text_trim_end(&x[0])),
+        NativeFun::new("substring", &["text", "start", "len"], |x| {
+            text_substring(&x[0], &x[1], &x[2])
+        }),
+        NativeFun::new("find", &["text", "pattern"], |x| text_find(&x[0], &x[1])),
+        NativeFun::new("replace", &["text", "from", "to"], |x| {
+            text_replace(&x[0], &x[1], &x[2])
+        }),
+        NativeFun::new("starts_with", &["text", "prefix"], |x| {
+            text_starts_with(&x[0], &x[1])
+        }),
+        NativeFun::new("ends_with", &["text", "suffix"], |x| {
+            text_ends_with(&x[0], &x[1])
+        }),
+        NativeFun::new("concat", &["text", "other"], |x| text_concat(&x[0], &x[1])),
+        NativeFun::new("split", &["text", "sep"], split_rel),
+        NativeFun::new("join", &["of", "sep"], join_rel),
+        NativeFun::new("pad_left", &["text", "width", "fill"], |x| {
+            text_pad_left(&x[0], &x[1], &x[2])
+        }),
+        NativeFun::new("pad_right", &["text", "width", "fill"], |x| {
+            text_pad_right(&x[0], &x[1], &x[2])
+        }),
+    ]
+}
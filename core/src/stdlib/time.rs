@@ -0,0 +1,286 @@
+use std::cell::Cell;
+use std::fmt::Write;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::error::*;
+use crate::stdlib::math::cast_num;
+use crate::stdlib::text::{any_null, text};
+use crate::stdlib::NativeFun;
+use crate::types::*;
+
+thread_local! {
+    //When set, now() & today() return it
+    static FROZEN: Cell<Option<TimeStamp>> = const { Cell::new(None) };
+}
+
+fn utc() -> FixedOffset {
+    FixedOffset::east(0)
+}
+
+/// Make now() & today() return always `at`, for tests
+pub fn freeze(at: TimeStamp) {
+    FROZEN.with(|x| x.set(Some(at)))
+}
+
+pub fn unfreeze() {
+    FROZEN.with(|x| x.set(None))
+}
+
+/// The current moment in UTC, unless frozen
+pub fn now() -> TimeStamp {
+    match FROZEN.with(|x| x.get()) {
+        Some(x) => x,
+        None => Utc::now().with_timezone(&utc()),
+    }
+}
+
+pub fn today() -> NaiveDate {
+    now().naive_local().date()
+}
+
+/// The parts of a date or time, also the units of truncation & durations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimePart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Weekday,
+    DayOfYear,
+}
+
+impl TimePart {
+    pub fn from_name(name: &str) -> ResultT<Self> {
+        let part = match name {
+            "year" => TimePart::Year,
+            "month" => TimePart::Month,
+            "day" => TimePart::Day,
+            "hour" => TimePart::Hour,
+            "minute" => TimePart::Minute,
+            "second" => TimePart::Second,
+            "weekday" => TimePart::Weekday,
+            "day_of_year" => TimePart::DayOfYear,
+            _ => return Err(Error::Domain(format!("{} is not a part of a date", name))),
+        };
+        Ok(part)
+    }
+}
+
+fn unsupported(part: TimePart, of: &Scalar) -> Error {
+    Error::Unsupported(format!("{:?}", part), of.kind())
+}
+
+fn parse_with<T, E, F>(of: &Scalar, pattern: &Scalar, kind: DataType, parse: F) -> ResultT<Scalar>
+where
+    F: FnOnce(&str, &str) -> Result<T, E>,
+    Scalar: From<T>,
+{
+    if any_null(&[of, pattern]) {
+        return Ok(Scalar::None);
+    }
+    let x =
+        parse(text(of)?, text(pattern)?).map_err(|_| Error::InvalidCast(DataType::UTF8, kind))?;
+    Ok(x.into())
+}
+
+/// Parse with a strftime pattern, like "%Y-%m-%d"
+pub fn time_parse_date(of: &Scalar, pattern: &Scalar) -> ResultT<Scalar> {
+    parse_with(of, pattern, DataType::Date, NaiveDate::parse_from_str)
+}
+
+pub fn time_parse_time(of: &Scalar, pattern: &Scalar) -> ResultT<Scalar> {
+    parse_with(of, pattern, DataType::Time, NaiveTime::parse_from_str)
+}
+
+/// Without a offset in the pattern, the text is taken as UTC
+pub fn time_parse_datetime(of: &Scalar, pattern: &Scalar) -> ResultT<Scalar> {
+    parse_with(of, pattern, DataType::DateTime, |x, p| {
+        DateTime::parse_from_str(x, p)
+            .or_else(|_| NaiveDateTime::parse_from_str(x, p).map(|x| DateTime::from_utc(x, utc())))
+    })
+}
+
+/// Format with a strftime pattern. Is a error if the pattern is invalid or
+/// ask for parts the value not have, like the hour of a date
+pub fn time_format(of: &Scalar, pattern: &Scalar) -> ResultT<Scalar> {
+    if any_null(&[of, pattern]) {
+        return Ok(Scalar::None);
+    }
+    let p = text(pattern)?;
+    let mut s = String::new();
+    let done = match of {
+        Scalar::Date(x) => write!(s, "{}", x.format(p)),
+        Scalar::Time(x) => write!(s, "{}", x.format(p)),
+        Scalar::DateTime(x) => write!(s, "{}", x.format(p)),
+        x => return Err(Error::Unsupported("format".into(), x.kind())),
+    };
+    done.map_err(|_| Error::Domain(format!("{} is not a pattern for {}", p, of.kind())))?;
+    Ok(s.into())
+}
+
+/// The part as a number. The parts of a DateTime are in his own offset
+pub fn time_part(of: &Scalar, part: TimePart) -> ResultT<Scalar> {
+    let (date, time) = match of {
+        Scalar::None => return Ok(Scalar::None),
+        Scalar::Date(x) => (Some(*x), None),
+        Scalar::Time(x) => (None, Some(*x)),
+        Scalar::DateTime(x) => (Some(x.naive_local().date()), Some(x.naive_local().time())),
+        x => return Err(Error::Unsupported("part".into(), x.kind())),
+    };
+    let value = match (part, date, time) {
+        (TimePart::Year, Some(d), _) => d.year() as i64,
+        (TimePart::Month, Some(d), _) => d.month().into(),
+        (TimePart::Day, Some(d), _) => d.day().into(),
+        (TimePart::Weekday, Some(d), _) => d.weekday().number_from_monday().into(),
+        (TimePart::DayOfYear, Some(d), _) => d.ordinal().into(),
+        (TimePart::Hour, _, Some(t)) => t.hour().into(),
+        (TimePart::Minute, _, Some(t)) => t.minute().into(),
+        (TimePart::Second, _, Some(t)) => t.second().into(),
+        _ => return Err(unsupported(part, of)),
+    };
+    Ok(Scalar::I64(value))
+}
+
+fn trunc_date(of: NaiveDate, part: TimePart) -> Option<NaiveDate> {
+    match part {
+        TimePart::Year => Some(NaiveDate::from_ymd(of.year(), 1, 1)),
+        TimePart::Month => Some(NaiveDate::from_ymd(of.year(), of.month(), 1)),
+        TimePart::Day => Some(of),
+        _ => None,
+    }
+}
+
+fn trunc_time(of: NaiveTime, part: TimePart) -> Option<NaiveTime> {
+    match part {
+        TimePart::Year | TimePart::Month | TimePart::Day => Some(NaiveTime::from_hms(0, 0, 0)),
+        TimePart::Hour => Some(NaiveTime::from_hms(of.hour(), 0, 0)),
+        TimePart::Minute => Some(NaiveTime::from_hms(of.hour(), of.minute(), 0)),
+        TimePart::Second => Some(NaiveTime::from_hms(of.hour(), of.minute(), of.second())),
+        _ => None,
+    }
+}
+
+/// The start of the year, month, day, hour, minute or second of the value
+pub fn time_trunc(of: &Scalar, part: TimePart) -> ResultT<Scalar> {
+    let x = match of {
+        Scalar::None => return Ok(Scalar::None),
+        Scalar::Date(x) => trunc_date(*x, part).map(Scalar::from),
+        Scalar::Time(x) => match part {
+            TimePart::Hour | TimePart::Minute | TimePart::Second => trunc_time(*x, part),
+            _ => None,
+        }
+        .map(Scalar::from),
+        Scalar::DateTime(x) => {
+            let local = x.naive_local();
+            let date = match part {
+                TimePart::Year | TimePart::Month => trunc_date(local.date(), part),
+                _ => Some(local.date()),
+            };
+            let time = trunc_time(local.time(), part);
+            date.and_then(|d| time.map(|t| d.and_time(t)))
+                .and_then(|local| x.offset().from_local_datetime(&local).single())
+                .map(Scalar::from)
+        }
+        x => return Err(Error::Unsupported("trunc".into(), x.kind())),
+    };
+    x.ok_or_else(|| unsupported(part, of))
+}
+
+/// A duration of `amount` units. Years & months have not a fixed length, so
+/// are not units
+pub fn time_duration(amount: &Scalar, unit: TimePart) -> ResultT<Scalar> {
+    if any_null(&[amount]) {
+        return Ok(Scalar::None);
+    }
+    let millis: i64 = match unit {
+        TimePart::Day => 86_400_000,
+        TimePart::Hour => 3_600_000,
+        TimePart::Minute => 60_000,
+        TimePart::Second => 1_000,
+        _ => {
+            return Err(Error::Unsupported(
+                format!("{:?}", unit),
+                DataType::Duration,
+            ))
+        }
+    };
+    let amount = match cast_num(amount, DataType::I64)? {
+        Scalar::I64(x) => x,
+        x => return Err(Error::InvalidCast(x.kind(), DataType::I64)),
+    };
+    match amount.checked_mul(millis) {
+        Some(x) if x != i64::MIN => Ok(Duration::milliseconds(x).into()),
+        _ => Err(Error::Overflow(DataType::Duration)),
+    }
+}
+
+/// Date arithmetic for `+`: a point in time plus a duration, or two durations.
+/// None on overflow. The time of the day wrap around midnight
+pub fn time_add(x: &Scalar, y: &Scalar) -> ResultT<Option<Scalar>> {
+    let result = match (x, y) {
+        (Scalar::None, _) | (_, Scalar::None) => Some(Scalar::None),
+        (Scalar::Date(a), Scalar::Duration(b)) | (Scalar::Duration(b), Scalar::Date(a)) => {
+            a.checked_add_signed(*b).map(Scalar::from)
+        }
+        (Scalar::Time(a), Scalar::Duration(b)) | (Scalar::Duration(b), Scalar::Time(a)) => {
+            Some(a.overflowing_add_signed(*b).0.into())
+        }
+        (Scalar::DateTime(a), Scalar::Duration(b)) | (Scalar::Duration(b), Scalar::DateTime(a)) => {
+            a.checked_add_signed(*b).map(Scalar::from)
+        }
+        (Scalar::Duration(a), Scalar::Duration(b)) => a.checked_add(b).map(Scalar::from),
+        (a, b) => return Err(Error::TypeMismatch(a.kind(), b.kind())),
+    };
+    Ok(result)
+}
+
+/// Date arithmetic for `-`: the duration between two points in time of the
+/// same type, or a point in time minus a duration
+pub fn time_minus(x: &Scalar, y: &Scalar) -> ResultT<Option<Scalar>> {
+    let result = match (x, y) {
+        (Scalar::None, _) | (_, Scalar::None) => Some(Scalar::None),
+        (Scalar::Date(a), Scalar::Date(b)) => Some(a.signed_duration_since(*b).into()),
+        (Scalar::Time(a), Scalar::Time(b)) => Some(a.signed_duration_since(*b).into()),
+        (Scalar::DateTime(a), Scalar::DateTime(b)) => Some(a.signed_duration_since(*b).into()),
+        (Scalar::Date(a), Scalar::Duration(b)) => a.checked_sub_signed(*b).map(Scalar::from),
+        (Scalar::Time(a), Scalar::Duration(b)) => Some(a.overflowing_sub_signed(*b).0.into()),
+        (Scalar::DateTime(a), Scalar::Duration(b)) => a.checked_sub_signed(*b).map(Scalar::from),
+        (Scalar::Duration(a), Scalar::Duration(b)) => a.checked_sub(b).map(Scalar::from),
+        (a, b) => return Err(Error::TypeMismatch(a.kind(), b.kind())),
+    };
+    Ok(result)
+}
+
+fn part_of(name: &Scalar) -> ResultT<TimePart> {
+    TimePart::from_name(text(name)?)
+}
+
+pub fn functions() -> Vec<NativeFun> {
+    vec![
+        NativeFun::new("now", &[], |_| Ok(now().into())),
+        NativeFun::new("today", &[], |_| Ok(today().into())),
+        NativeFun::new("parse_date", &["text", "pattern"], |x| {
+            time_parse_date(&x[0], &x[1])
+        }),
+        NativeFun::new("parse_time", &["text", "pattern"], |x| {
+            time_parse_time(&x[0], &x[1])
+        }),
+        NativeFun::new("parse_datetime", &["text", "pattern"], |x| {
+            time_parse_datetime(&x[0], &x[1])
+        }),
+        NativeFun::new("format", &["of", "pattern"], |x| time_format(&x[0], &x[1])),
+        NativeFun::new("part", &["of", "part"], |x| {
+            time_part(&x[0], part_of(&x[1])?)
+        }),
+        NativeFun::new("trunc", &["of", "part"], |x| {
+            time_trunc(&x[0], part_of(&x[1])?)
+        }),
+        NativeFun::new("duration", &["amount", "unit"], |x| {
+            time_duration(&x[0], part_of(&x[1])?)
+        }),
+    ]
+}
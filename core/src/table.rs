@@ -1,9 +1,13 @@
-use std::collections::HashSet;
-
 use crate::dsl::*;
+use crate::error::*;
+use crate::relational::*;
 use crate::types::*;
 
 impl Relation for Table {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
     fn shape(&self) -> Shape {
         let (cols, rows) = self.size();
 
@@ -18,62 +22,85 @@ impl Relation for Table {
     }
 
     fn as_seq(&self) -> Seq {
-        Seq::new(self.schema.clone(), &self.shape(), ref_cell(self.rows()))
+        Seq::of_rows(self.schema.clone(), &self.shape(), self.clone())
     }
 
-    fn filter(&self, cmp: CmOp) -> Rel {
-        let apply = cmp.get_fn();
-        let data = self
-            .data
-            .iter()
-            .filter(|x| apply(&x[cmp.lhs], &cmp.rhs))
-            .cloned();
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(self.schema.len())?;
+        let data = self.data.iter().filter(|x| pred.eval(x)).cloned();
         let rel = Self::new(self.schema.clone(), data.collect());
-        rel.into()
+        Ok(rel.into())
+    }
+
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        let data = union_rows(&self.data, aligned_rows(&self.schema, other)?);
+        Ok(Self::new(self.schema.clone(), data).into())
+    }
+
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        let data = diff_rows(&self.data, aligned_rows(&self.schema, other)?);
+        Ok(Self::new(self.schema.clone(), data).into())
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        let data = intersect_rows(&self.data, aligned_rows(&self.schema, other)?);
+        Ok(Self::new(self.schema.clone(), data).into())
+    }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        let keys = sort_positions(&self.schema, keys)?;
+        let mut data = self.data.clone();
+        sort_rows(&keys, &mut data);
+
+        Ok(Self::new(self.schema.clone(), data).into())
     }
 
-    fn union(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::Table(b) => {
-                let data = self.data.iter().chain(b.data.iter()).cloned();
-                Self::new(self.schema.clone(), data.collect()).into()
-            }
-            _ => unimplemented!(),
-        }
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        let data = limit_rows(&self.data, skip, take);
+        Ok(Self::new(self.schema.clone(), data).into())
     }
 
-    fn diff(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::Table(b) => {
-                let a = self.as_set();
-                let b = b.as_set();
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        let pos = distinct_positions(&self.schema, on)?;
+        let data = distinct_rows(&self.data, |x| project_row(&pos, x));
 
-                let result = a.difference(&b);
-                Self::new(self.schema.clone(), result.cloned().collect()).into()
-            }
-            _ => unimplemented!(),
-        }
+        Ok(Self::new(self.schema.clone(), data).into())
     }
 
-    fn intersect(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::Table(b) => {
-                let a = self.as_set();
-                let b = b.as_set();
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        let pos = self.schema.resolve_pos_many(cols)?;
+        let schema = self.schema.only(&pos);
+        let data = self.data.iter().map(|x| project_row(&pos, x));
 
-                let result = a.intersection(&b);
-                Self::new(self.schema.clone(), result.cloned().collect()).into()
-            }
-            _ => unimplemented!(),
-        }
+        Ok(Self::new(schema, data.collect()).into())
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        let schema = rename_schema(&self.schema, change)?;
+        Ok(Self::new(schema, self.data.clone()).into())
     }
 }
 
 impl Table {
+    /// Unchecked: the rows are not validated against the schema, not even the
+    /// not-null columns. The operations use it for rows that already conform,
+    /// use try_new for the data that come from outside
     pub fn new(schema: Schema, data: Vec<Col>) -> Self {
         Table { schema, data }
     }
 
+    /// Like new, but fail on the first row that not conform to the schema
+    pub fn try_new(schema: Schema, data: Vec<Col>) -> ResultT<Self> {
+        let data = schema.conform_rows(data, false)?;
+        Ok(Self::new(schema, data))
+    }
+
+    /// Like try_new, casting up the numbers to the type of the column
+    pub fn try_cast(schema: Schema, data: Vec<Col>) -> ResultT<Self> {
+        let data = schema.conform_rows(data, true)?;
+        Ok(Self::new(schema, data))
+    }
+
     pub fn empty(kind: DataType) -> Self {
         let schema = schema_it(kind);
         let data = vec![];
@@ -81,15 +108,7 @@ impl Table {
     }
 
     fn size(&self) -> (usize, usize) {
-        let rows = self.data.len();
-
-        let cols = if rows > 0 { self.data[0].len() } else { 0 };
-
-        (cols, rows)
-    }
-
-    fn as_set(&self) -> HashSet<Col> {
-        self.data.iter().cloned().collect()
+        (self.schema.len(), self.data.len())
     }
 }
 
@@ -108,4 +127,9 @@ impl RelIter for RowsIter<Table> {
         let pos = self.pos - 1;
         self.rel.data[pos].clone()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.rel.data.len().saturating_sub(self.pos);
+        (left, Some(left))
+    }
 }
@@ -1,7 +1,8 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 
-use std::cell::RefCell;
+use std::cmp;
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::fmt::Debug;
@@ -13,18 +14,30 @@ use decorum::R64;
 extern crate bit_vec;
 use self::bit_vec::BitVec;
 
+extern crate bytes;
+use bytes::Bytes;
+
 extern crate chrono;
 use chrono::prelude::*;
+use chrono::Duration;
 
 extern crate rust_decimal;
 use rust_decimal::Decimal;
 
-#[derive(Debug, Clone, Copy)]
+use crate::error::*;
+use crate::stdlib::math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Join {
     Left,
     Right,
     Inner,
-    Full, //, Natural, Cross
+    Full,
+    Natural,
+    Cross,
+    //Only return the columns of the left
+    Semi,
+    Anti,
 }
 
 impl Join {
@@ -32,8 +45,8 @@ impl Join {
         match self {
             Join::Left => !is_left,
             Join::Right => is_left,
-            Join::Inner => false,
             Join::Full => true,
+            Join::Inner | Join::Natural | Join::Cross | Join::Semi | Join::Anti => false,
         }
     }
 }
@@ -45,16 +58,56 @@ pub enum Shape {
     Row(usize),
     Vector(usize),
     Table(usize, usize),
+    //Rows not known until consumed, like the lines of a file
+    Stream(usize),
 }
 
 impl Shape {
     pub fn size(&self) -> (usize, usize) {
         match self {
             Shape::Scalar => (1, 1),
-            Shape::KV(rows) => (1, *rows),
+            Shape::KV(rows) => (2, *rows),
             Shape::Row(cols) => (*cols, 1),
             Shape::Vector(rows) => (1, *rows),
             Shape::Table(cols, rows) => (*cols, *rows),
+            Shape::Stream(cols) => (*cols, 0),
+        }
+    }
+
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Shape::Stream(_))
+    }
+
+    /// The rows as the bounds of a iterator: a filter can drop rows, so only
+    /// the upper is known, and a stream has none
+    pub fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Shape::Stream(_) => (0, None),
+            x => (0, Some(x.size().1)),
+        }
+    }
+
+    /// The shape with the same columns but other amount of rows, as after a limit
+    pub fn with_rows(&self, rows: usize) -> Shape {
+        match self {
+            Shape::KV(_) => Shape::KV(rows),
+            Shape::Vector(_) => Shape::Vector(rows),
+            Shape::Table(cols, _) => Shape::Table(*cols, rows),
+            Shape::Stream(_) => *self,
+            Shape::Scalar | Shape::Row(_) if rows == 1 => *self,
+            Shape::Scalar => Shape::Vector(rows),
+            Shape::Row(cols) => Shape::Table(*cols, rows),
+        }
+    }
+
+    /// The shape with the same rows but other amount of columns, as after a projection
+    pub fn with_cols(&self, cols: usize) -> Shape {
+        let (_, rows) = self.size();
+        match self {
+            Shape::Scalar | Shape::Vector(_) if cols == 1 => *self,
+            Shape::Row(_) => Shape::Row(cols),
+            Shape::Stream(_) => Shape::Stream(cols),
+            _ => Shape::Table(cols, rows),
         }
     }
 }
@@ -65,13 +118,111 @@ pub enum BinOp {
     Minus,
     Mul,
     Div,
+    Mod,
+    //Division truncated toward zero
+    IntDiv,
+    Pow,
+    //Bitwise, only for integers
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl BinOp {
+    pub fn get_fn(self) -> &'static BinExpr {
+        match self {
+            BinOp::Add => &math_add,
+            BinOp::Minus => &math_minus,
+            BinOp::Mul => &math_mul,
+            BinOp::Div => &math_div,
+            BinOp::Mod => &math_mod,
+            BinOp::IntDiv => &math_int_div,
+            BinOp::Pow => &math_pow,
+            BinOp::BitAnd => &math_bit_and,
+            BinOp::BitOr => &math_bit_or,
+            BinOp::BitXor => &math_bit_xor,
+            BinOp::Shl => &math_shl,
+            BinOp::Shr => &math_shr,
+        }
+    }
+
+    pub fn eval(self, lhs: &Scalar, rhs: &Scalar, mode: OnError) -> ResultT<Scalar> {
+        match self {
+            BinOp::Add => math_add_with(lhs, rhs, mode),
+            BinOp::Minus => math_minus_with(lhs, rhs, mode),
+            BinOp::Mul => math_mul_with(lhs, rhs, mode),
+            BinOp::Div => math_div_with(lhs, rhs, mode),
+            BinOp::Mod => math_mod_with(lhs, rhs, mode),
+            BinOp::IntDiv => math_int_div_with(lhs, rhs, mode),
+            BinOp::Pow => math_pow_with(lhs, rhs, mode),
+            BinOp::BitAnd => math_bit_and_with(lhs, rhs, mode),
+            BinOp::BitOr => math_bit_or_with(lhs, rhs, mode),
+            BinOp::BitXor => math_bit_xor_with(lhs, rhs, mode),
+            BinOp::Shl => math_shl_with(lhs, rhs, mode),
+            BinOp::Shr => math_shr_with(lhs, rhs, mode),
+        }
+    }
+
+    /// The type of the result. On mixed operands, is the larger in the DataType
+    /// order, but a date +/- a duration is a date, and a date - a date a duration
+    pub fn kind(self, lhs: DataType, rhs: DataType) -> DataType {
+        let point =
+            |x: &DataType| matches!(x, DataType::Date | DataType::Time | DataType::DateTime);
+        match (self, lhs, rhs) {
+            (BinOp::Minus, a, b) if a == b && point(&a) => DataType::Duration,
+            (_, a, DataType::Duration) if point(&a) => a,
+            (_, DataType::Duration, b) if point(&b) => b,
+            (_, a, b) => cmp::max(a, b),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Abs,
+    //Rounding at the decimal places
+    Round(u32),
+    Floor(u32),
+    Ceil(u32),
+    Trunc(u32),
+    //Computed as F64
+    Sqrt,
+    Log,
+    Exp,
+}
+
+impl UnaryOp {
+    /// The type of the result for a operand of `kind`
+    pub fn kind(self, kind: DataType) -> DataType {
+        match self {
+            UnaryOp::Sqrt | UnaryOp::Log | UnaryOp::Exp => DataType::F64,
+            _ => kind,
+        }
+    }
+
+    pub fn eval(self, of: &Scalar, mode: OnError) -> ResultT<Scalar> {
+        math_unary(self, of, mode)
+    }
+}
+
+/// What to do when a math operation overflow or divide by zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    #[default]
+    Fail,
+    //Clamp to the min or max of the type. Divide by zero still fail
+    Saturate,
+    //Return null
+    Null,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum LogicOp {
     And,
     Or,
-    Not,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -84,6 +235,31 @@ pub enum CompareOp {
     GreaterEq,
 }
 
+impl CompareOp {
+    pub fn get_fn(self) -> &'static BoolExpr {
+        match self {
+            CompareOp::Eq => &PartialEq::eq,
+            CompareOp::NotEq => &PartialEq::ne,
+            CompareOp::Less => &PartialOrd::lt,
+            CompareOp::LessEq => &PartialOrd::le,
+            CompareOp::Greater => &PartialOrd::gt,
+            CompareOp::GreaterEq => &PartialOrd::ge,
+        }
+    }
+
+    /// Compare two numbers of different types as the larger of both, like a
+    /// I32 against a I64. The others by the order of Scalar
+    pub fn eval(self, lhs: &Scalar, rhs: &Scalar) -> bool {
+        let apply = self.get_fn();
+        if std::mem::discriminant(lhs) != std::mem::discriminant(rhs) {
+            if let Ok(Some((lhs, rhs))) = promote(lhs, rhs) {
+                return apply(&lhs, &rhs);
+            }
+        }
+        apply(lhs, rhs)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CrudOp {
     Create,
@@ -102,6 +278,25 @@ pub enum KeyValue {
     Value,
 }
 
+/// Where the `Scalar::None` values go when sorting, no matter the direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    First,
+    Last,
+    CountDistinct,
+}
+
 #[derive(Debug, Clone)]
 pub enum SetQuery {
     Union,
@@ -111,7 +306,7 @@ pub enum SetQuery {
 
 //NOTE: This define a total order, so it matter what is the order
 //of the enum! The overall sorting order is defined as:
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum DataType {
     None,
     Any,
@@ -123,24 +318,65 @@ pub enum DataType {
     F64,
     Decimal,
     //Dates
+    Date,
+    Time,
     DateTime,
+    Duration,
     //Text
     UTF8,
-    //Complex
-    Rel, // Planed: BitVec, Blob, Sum(DataType), Product(DataType), Rel(Vec<Field>)
+    //Binary
+    BitVec,
+    Blob,
+    //Complex. A sum list his alternatives as tag & type, a product the type
+    //of each element
+    Sum(Vec<(String, DataType)>),
+    Product(Vec<DataType>),
+    //A nested relation, typed by his schema
+    Rel(Schema),
+}
+
+impl DataType {
+    /// If a value of `kind` can be stored as this type. A value of a sum only
+    /// know his own alternative, and a null element fit in a product
+    pub fn accepts(&self, kind: &DataType) -> bool {
+        let fit = |of: &DataType, kind: &DataType| *kind == DataType::None || of.accepts(kind);
+        match (self, kind) {
+            (DataType::Any, _) => true,
+            (DataType::Sum(alts), DataType::Sum(used)) => used
+                .iter()
+                .all(|(tag, kind)| alts.iter().any(|(x, of)| x == tag && fit(of, kind))),
+            (DataType::Product(a), DataType::Product(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(of, kind)| fit(of, kind))
+            }
+            (a, b) => a == b,
+        }
+    }
 }
 
 impl fmt::Display for DataType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            DataType::Sum(alts) => {
+                let alts: Vec<_> = alts.iter().map(|(x, of)| format!("{}:{}", x, of)).collect();
+                write!(f, "Sum({})", alts.join(" | "))
+            }
+            DataType::Product(of) => {
+                let of: Vec<_> = of.iter().map(|x| x.to_string()).collect();
+                write!(f, "Product({})", of.join(", "))
+            }
+            DataType::Rel(schema) => write!(f, "Rel({})", schema),
+            x => write!(f, "{:?}", x),
+        }
     }
 }
 
-pub type TimeStamp = DateTime<Local>;
+//Always with a explicit offset, to not depend on the host time zone
+pub type TimeStamp = DateTime<FixedOffset>;
 
 //NOTE: The order of this enum must match DataType
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub enum Scalar {
+    #[default]
     None, //null
     Bool(bool),
     //Numeric
@@ -149,16 +385,42 @@ pub enum Scalar {
     I64(i64),
     F64(R64),
     Decimal(Decimal),
+    Date(NaiveDate),
+    Time(NaiveTime),
     DateTime(TimeStamp),
+    Duration(Duration),
     UTF8(String),
+    //Binary, boxed to keep the size of Scalar small
+    BitVec(Rc<BitVec>),
+    Blob(Rc<Bytes>),
     //Complex
+    Sum(Rc<Variant>),
+    Product(Rc<Vec<Scalar>>),
     Rel(Rc<Rel>),
 }
 
+/// A value of a sum type: one of the alternatives, named by the tag
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Variant {
+    pub tag: String,
+    pub value: Scalar,
+}
+
+impl Variant {
+    pub fn new(tag: &str, value: Scalar) -> Self {
+        Variant {
+            tag: tag.to_string(),
+            value,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Rel {
     One(Scalar),
+    Row(Row),
     Vector(Vector),
+    KV(BTree),
     Range(Range),
     Table(Table),
     Seq(Seq),
@@ -166,7 +428,7 @@ pub enum Rel {
 
 //Type Alias...
 pub type BoolExpr = dyn Fn(&Scalar, &Scalar) -> bool;
-pub type BinExpr = dyn Fn(&Scalar, &Scalar) -> Scalar;
+pub type BinExpr = dyn Fn(&Scalar, &Scalar) -> ResultT<Scalar>;
 pub type UnaryExpr = dyn Fn(&Scalar) -> Scalar;
 pub type Col = Vec<Scalar>;
 pub type BCol = Vec<Box<Scalar>>;
@@ -185,9 +447,10 @@ pub enum ColumnName {
 pub struct Field {
     pub name: String,
     pub kind: DataType,
+    pub nullable: bool,
 }
 
-#[derive(Debug, Clone, PartialOrd, Ord)]
+#[derive(Debug, Clone)]
 pub struct Schema {
     pub columns: Vec<Field>,
 }
@@ -198,6 +461,13 @@ pub struct Vector {
     pub data: Col,
 }
 
+/// A single row, like a record
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Row {
+    pub schema: Schema,
+    pub data: Col,
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct Range {
     pub schema: Schema,
@@ -206,6 +476,7 @@ pub struct Range {
     pub step: usize,
 }
 
+/// Key/value pairs with unique keys, sorted by key
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct BTree {
     pub schema: Schema,
@@ -218,6 +489,239 @@ pub struct Table {
     pub data: Vec<Col>,
 }
 
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub col: ColumnName,
+    pub asc: bool,
+    pub nulls: Nulls,
+}
+
+impl SortKey {
+    pub fn new(col: ColumnName, asc: bool, nulls: Nulls) -> Self {
+        SortKey { col, asc, nulls }
+    }
+
+    /// Ascending, with nulls last
+    pub fn asc(col: ColumnName) -> Self {
+        Self::new(col, true, Nulls::Last)
+    }
+
+    /// Descending, with nulls first
+    pub fn desc(col: ColumnName) -> Self {
+        Self::new(col, false, Nulls::First)
+    }
+
+    pub fn nulls(self, nulls: Nulls) -> Self {
+        SortKey { nulls, ..self }
+    }
+
+    pub fn compare(&self, a: &Scalar, b: &Scalar) -> Ordering {
+        let nulls_first = self.nulls == Nulls::First;
+        match (a, b) {
+            (Scalar::None, Scalar::None) => Ordering::Equal,
+            (Scalar::None, _) if nulls_first => Ordering::Less,
+            (Scalar::None, _) => Ordering::Greater,
+            (_, Scalar::None) if nulls_first => Ordering::Greater,
+            (_, Scalar::None) => Ordering::Less,
+            (a, b) if self.asc => a.cmp(b),
+            (a, b) => b.cmp(a),
+        }
+    }
+}
+
+/// Aggregate a column per group. Without a name, the result column is named
+/// after the function and the column, like `sum_total`
+#[derive(Debug, Clone)]
+pub struct Agg {
+    pub op: Aggregate,
+    pub col: ColumnName,
+    pub name: Option<String>,
+    //What a overflow in sum or avg return
+    pub on_error: OnError,
+}
+
+impl Agg {
+    pub fn new(op: Aggregate, col: ColumnName) -> Self {
+        Agg {
+            op,
+            col,
+            name: None,
+            on_error: OnError::Fail,
+        }
+    }
+
+    pub fn named(self, name: &str) -> Self {
+        Agg {
+            name: Some(name.to_string()),
+            ..self
+        }
+    }
+
+    pub fn on_error(self, mode: OnError) -> Self {
+        Agg {
+            on_error: mode,
+            ..self
+        }
+    }
+
+    pub fn count(col: ColumnName) -> Self {
+        Self::new(Aggregate::Count, col)
+    }
+    pub fn sum(col: ColumnName) -> Self {
+        Self::new(Aggregate::Sum, col)
+    }
+    pub fn min(col: ColumnName) -> Self {
+        Self::new(Aggregate::Min, col)
+    }
+    pub fn max(col: ColumnName) -> Self {
+        Self::new(Aggregate::Max, col)
+    }
+    pub fn avg(col: ColumnName) -> Self {
+        Self::new(Aggregate::Avg, col)
+    }
+    pub fn first(col: ColumnName) -> Self {
+        Self::new(Aggregate::First, col)
+    }
+    pub fn last(col: ColumnName) -> Self {
+        Self::new(Aggregate::Last, col)
+    }
+    pub fn count_distinct(col: ColumnName) -> Self {
+        Self::new(Aggregate::CountDistinct, col)
+    }
+}
+
+/// A expression evaluated per row, like `qty * price`
+#[derive(Debug, Clone)]
+pub enum RowExpr {
+    Column(ColumnName),
+    Value(Scalar),
+    BinOp(BinOp, Box<RowExpr>, Box<RowExpr>),
+    Unary(UnaryOp, Box<RowExpr>),
+    //The first not null
+    Coalesce(Vec<RowExpr>),
+}
+
+impl RowExpr {
+    pub fn col(of: ColumnName) -> Self {
+        RowExpr::Column(of)
+    }
+
+    pub fn value(of: Scalar) -> Self {
+        RowExpr::Value(of)
+    }
+
+    pub fn bin_op(op: BinOp, lhs: RowExpr, rhs: RowExpr) -> Self {
+        RowExpr::BinOp(op, lhs.into(), rhs.into())
+    }
+
+    pub fn unary(op: UnaryOp, of: RowExpr) -> Self {
+        RowExpr::Unary(op, of.into())
+    }
+
+    pub fn coalesce(of: &[RowExpr]) -> Self {
+        RowExpr::Coalesce(of.to_vec())
+    }
+
+    /// Turn the column names into positions, so it can be evaluated
+    pub fn resolve(&self, schema: &Schema) -> ResultT<Self> {
+        Ok(match self {
+            RowExpr::Column(x) => RowExpr::Column(ColumnName::Pos(schema.resolve_pos(x)?)),
+            RowExpr::Value(x) => RowExpr::Value(x.clone()),
+            RowExpr::BinOp(op, lhs, rhs) => {
+                Self::bin_op(*op, lhs.resolve(schema)?, rhs.resolve(schema)?)
+            }
+            RowExpr::Unary(op, of) => Self::unary(*op, of.resolve(schema)?),
+            RowExpr::Coalesce(of) => RowExpr::Coalesce(
+                of.iter()
+                    .map(|x| x.resolve(schema))
+                    .collect::<ResultT<_>>()?,
+            ),
+        })
+    }
+
+    /// The type of the result
+    pub fn kind(&self, schema: &Schema) -> ResultT<DataType> {
+        Ok(match self {
+            RowExpr::Column(x) => schema[schema.resolve_pos(x)?].kind.clone(),
+            RowExpr::Value(x) => x.kind(),
+            RowExpr::BinOp(op, lhs, rhs) => op.kind(lhs.kind(schema)?, rhs.kind(schema)?),
+            RowExpr::Unary(op, of) => op.kind(of.kind(schema)?),
+            RowExpr::Coalesce(of) => {
+                let mut kind = DataType::None;
+                for x in of {
+                    kind = x.kind(schema)?;
+                    if kind != DataType::None {
+                        break;
+                    }
+                }
+                kind
+            }
+        })
+    }
+
+    pub fn eval(&self, row: &[Scalar]) -> ResultT<Scalar> {
+        self.eval_with(row, OnError::Fail)
+    }
+
+    /// Evaluate, with `mode` deciding what a overflow or divide by zero return
+    pub fn eval_with(&self, row: &[Scalar], mode: OnError) -> ResultT<Scalar> {
+        match self {
+            RowExpr::Column(ColumnName::Pos(x)) => row
+                .get(*x)
+                .cloned()
+                .ok_or(Error::ColumnOutOfRange(*x, row.len())),
+            RowExpr::Column(ColumnName::Name(x)) => Err(Error::FieldNotFound(x.clone())),
+            RowExpr::Value(x) => Ok(x.clone()),
+            RowExpr::BinOp(op, lhs, rhs) => {
+                op.eval(&lhs.eval_with(row, mode)?, &rhs.eval_with(row, mode)?, mode)
+            }
+            RowExpr::Unary(op, of) => op.eval(&of.eval_with(row, mode)?, mode),
+            RowExpr::Coalesce(of) => {
+                for x in of {
+                    let value = x.eval_with(row, mode)?;
+                    if value != Scalar::None {
+                        return Ok(value);
+                    }
+                }
+                Ok(Scalar::None)
+            }
+        }
+    }
+}
+
+/// Compare a column of the left relation against one of the right
+#[derive(Debug, Clone)]
+pub struct JoinCmp {
+    pub op: CompareOp,
+    pub lhs: ColumnName,
+    pub rhs: ColumnName,
+}
+
+impl JoinCmp {
+    pub fn new(op: CompareOp, lhs: ColumnName, rhs: ColumnName) -> Self {
+        JoinCmp { op, lhs, rhs }
+    }
+
+    pub fn eq(lhs: ColumnName, rhs: ColumnName) -> Self {
+        Self::new(CompareOp::Eq, lhs, rhs)
+    }
+    pub fn not(lhs: ColumnName, rhs: ColumnName) -> Self {
+        Self::new(CompareOp::NotEq, lhs, rhs)
+    }
+    pub fn less(lhs: ColumnName, rhs: ColumnName) -> Self {
+        Self::new(CompareOp::Less, lhs, rhs)
+    }
+    pub fn less_eq(lhs: ColumnName, rhs: ColumnName) -> Self {
+        Self::new(CompareOp::LessEq, lhs, rhs)
+    }
+    pub fn greater(lhs: ColumnName, rhs: ColumnName) -> Self {
+        Self::new(CompareOp::Greater, lhs, rhs)
+    }
+    pub fn greater_eq(lhs: ColumnName, rhs: ColumnName) -> Self {
+        Self::new(CompareOp::GreaterEq, lhs, rhs)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CmOp {
     pub op: CompareOp,
@@ -250,55 +754,296 @@ impl CmOp {
     }
 
     pub fn get_fn(&self) -> &BoolExpr {
-        match self.op {
-            CompareOp::Eq => &PartialEq::eq,
-            CompareOp::NotEq => &PartialEq::ne,
-            CompareOp::Less => &PartialOrd::lt,
-            CompareOp::LessEq => &PartialOrd::le,
-            CompareOp::Greater => &PartialOrd::gt,
-            CompareOp::GreaterEq => &PartialOrd::ge,
+        self.op.get_fn()
+    }
+
+    pub fn eval(&self, row: &[Scalar]) -> bool {
+        self.op.eval(&row[self.lhs], &self.rhs)
+    }
+
+    /// Like in SQL, a comparison with a null is unknown
+    pub fn eval_unknown(&self, row: &[Scalar]) -> Option<bool> {
+        compare_unknown(self.op, &row[self.lhs], &self.rhs)
+    }
+}
+
+fn compare_unknown(op: CompareOp, lhs: &Scalar, rhs: &Scalar) -> Option<bool> {
+    if *lhs == Scalar::None || *rhs == Scalar::None {
+        None
+    } else {
+        Some(op.eval(lhs, rhs))
+    }
+}
+
+/// A boolean expression evaluated per row, like `a > 1 and (b = "x" or not c)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    //Column against a constant
+    Cmp(CmOp),
+    //Column against column
+    Cols(CompareOp, usize, usize),
+    //And & Or of all the predicates
+    Logic(LogicOp, Vec<Predicate>),
+    Not(Box<Predicate>),
+    IsNull(usize),
+    //Opt-in SQL logic: the nulls are unknown, and only a true keep the row
+    ThreeValued(Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn cols(op: CompareOp, lhs: usize, rhs: usize) -> Self {
+        Predicate::Cols(op, lhs, rhs)
+    }
+
+    /// The boolean column is true
+    pub fn is_true(col: usize) -> Self {
+        CmOp::eq(col, Scalar::Bool(true).into()).into()
+    }
+
+    pub fn and(of: Vec<Predicate>) -> Self {
+        Predicate::Logic(LogicOp::And, of)
+    }
+
+    pub fn or(of: Vec<Predicate>) -> Self {
+        Predicate::Logic(LogicOp::Or, of)
+    }
+
+    pub fn negate(of: Predicate) -> Self {
+        Predicate::Not(Box::new(of))
+    }
+
+    pub fn is_null(col: usize) -> Self {
+        Predicate::IsNull(col)
+    }
+
+    pub fn not_null(col: usize) -> Self {
+        Self::negate(Self::is_null(col))
+    }
+
+    /// Evaluate with the three-valued logic of SQL, so `null = null` is unknown.
+    /// Without it, null is the smallest value and equal to itself
+    pub fn three_valued(of: Predicate) -> Self {
+        Predicate::ThreeValued(Box::new(of))
+    }
+
+    /// Check the columns exist in a relation of `width` columns
+    pub fn check(&self, width: usize) -> ResultT<()> {
+        let out = |x: usize| {
+            if x < width {
+                Ok(())
+            } else {
+                Err(Error::ColumnOutOfRange(x, width))
+            }
+        };
+        match self {
+            Predicate::Cmp(cmp) => out(cmp.lhs),
+            Predicate::Cols(_, lhs, rhs) => out(*lhs).and(out(*rhs)),
+            Predicate::Logic(_, of) => of.iter().try_for_each(|x| x.check(width)),
+            Predicate::Not(of) => of.check(width),
+            Predicate::IsNull(col) => out(*col),
+            Predicate::ThreeValued(of) => of.check(width),
+        }
+    }
+
+    pub fn eval(&self, row: &[Scalar]) -> bool {
+        match self {
+            Predicate::Cmp(cmp) => cmp.eval(row),
+            Predicate::Cols(op, lhs, rhs) => op.eval(&row[*lhs], &row[*rhs]),
+            Predicate::Logic(LogicOp::And, of) => of.iter().all(|x| x.eval(row)),
+            Predicate::Logic(LogicOp::Or, of) => of.iter().any(|x| x.eval(row)),
+            Predicate::Not(of) => !of.eval(row),
+            Predicate::IsNull(col) => row[*col] == Scalar::None,
+            Predicate::ThreeValued(of) => of.eval_unknown(row) == Some(true),
+        }
+    }
+
+    /// The three-valued logic, None is unknown
+    pub fn eval_unknown(&self, row: &[Scalar]) -> Option<bool> {
+        match self {
+            Predicate::Cmp(cmp) => cmp.eval_unknown(row),
+            Predicate::Cols(op, lhs, rhs) => compare_unknown(*op, &row[*lhs], &row[*rhs]),
+            //false and unknown is false, true or unknown is true
+            Predicate::Logic(LogicOp::And, of) => {
+                let all: Vec<_> = of.iter().map(|x| x.eval_unknown(row)).collect();
+                if all.contains(&Some(false)) {
+                    Some(false)
+                } else if all.contains(&None) {
+                    None
+                } else {
+                    Some(true)
+                }
+            }
+            Predicate::Logic(LogicOp::Or, of) => {
+                let all: Vec<_> = of.iter().map(|x| x.eval_unknown(row)).collect();
+                if all.contains(&Some(true)) {
+                    Some(true)
+                } else if all.contains(&None) {
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            Predicate::Not(of) => of.eval_unknown(row).map(|x| !x),
+            Predicate::IsNull(_) => Some(self.eval(row)),
+            Predicate::ThreeValued(of) => of.eval_unknown(row),
         }
     }
 }
 
+impl From<CmOp> for Predicate {
+    fn from(of: CmOp) -> Self {
+        Predicate::Cmp(of)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Query {
     //  To ask for all the rows, send a empty query
-    //    Distinct,
-    Where(CmOp),
-    Limit(usize, usize), // skip * limit
-    //Sort(bool, usize),   // true=ascending * col pos
-    //Select(Pos),
-    //Project(Col, String),
-    //    Project(Box<UnaryExpr>),
-    //Group(Pos),
-    //Join(Join),
+    Distinct(Vec<ColumnName>), // distinct on the columns, or all if empty
+    Where(Predicate),
+    Limit(usize, usize), // skip * take
+    Sort(Vec<SortKey>),
+    Select(Vec<ColumnName>),
+    Deselect(Vec<ColumnName>),
+    Extend(Vec<(String, RowExpr)>, OnError),
+    Rename(Vec<(ColumnName, String)>),
+    Group(Vec<ColumnName>, Vec<Agg>),
+    Nest(Vec<ColumnName>, String),
+    Unnest(ColumnName),
+    Join(Join, Vec<JoinCmp>, Rc<Rel>),
     Set(SetQuery, Rc<Rel>),
 }
 
 impl Query {
+    pub fn filter(of: Predicate) -> Self {
+        Query::Where(of)
+    }
+
     pub fn eq(lhs: usize, rhs: Scalar) -> Self {
-        Query::Where(CmOp::eq(lhs, rhs.into()))
+        Query::Where(CmOp::eq(lhs, rhs.into()).into())
     }
 
     pub fn not(lhs: usize, rhs: Scalar) -> Self {
-        Query::Where(CmOp::not(lhs, rhs.into()))
+        Query::Where(CmOp::not(lhs, rhs.into()).into())
     }
 
     pub fn less(lhs: usize, rhs: Scalar) -> Self {
-        Query::Where(CmOp::less(lhs, rhs.into()))
+        Query::Where(CmOp::less(lhs, rhs.into()).into())
     }
 
     pub fn less_eq(lhs: usize, rhs: Scalar) -> Self {
-        Query::Where(CmOp::less_eq(lhs, rhs.into()))
+        Query::Where(CmOp::less_eq(lhs, rhs.into()).into())
     }
 
     pub fn greater(lhs: usize, rhs: Scalar) -> Self {
-        Query::Where(CmOp::greater(lhs, rhs.into()))
+        Query::Where(CmOp::greater(lhs, rhs.into()).into())
     }
 
     pub fn greater_eq(lhs: usize, rhs: Scalar) -> Self {
-        Query::Where(CmOp::greater_eq(lhs, rhs.into()))
+        Query::Where(CmOp::greater_eq(lhs, rhs.into()).into())
+    }
+
+    pub fn is_null(col: usize) -> Self {
+        Query::Where(Predicate::is_null(col))
+    }
+
+    pub fn not_null(col: usize) -> Self {
+        Query::Where(Predicate::not_null(col))
+    }
+
+    pub fn sort(keys: &[SortKey]) -> Self {
+        Query::Sort(keys.to_vec())
+    }
+
+    pub fn sort_asc(col: ColumnName) -> Self {
+        Query::Sort(vec![SortKey::asc(col)])
+    }
+
+    pub fn sort_desc(col: ColumnName) -> Self {
+        Query::Sort(vec![SortKey::desc(col)])
+    }
+
+    pub fn select(cols: &[ColumnName]) -> Self {
+        Query::Select(cols.to_vec())
+    }
+
+    pub fn deselect(cols: &[ColumnName]) -> Self {
+        Query::Deselect(cols.to_vec())
+    }
+
+    pub fn limit(skip: usize, take: usize) -> Self {
+        Query::Limit(skip, take)
+    }
+
+    pub fn distinct() -> Self {
+        Query::Distinct(vec![])
+    }
+
+    pub fn distinct_on(cols: &[ColumnName]) -> Self {
+        Query::Distinct(cols.to_vec())
+    }
+
+    pub fn extend(name: &str, expr: RowExpr) -> Self {
+        Self::extend_with(name, expr, OnError::Fail)
+    }
+
+    pub fn extend_with(name: &str, expr: RowExpr, mode: OnError) -> Self {
+        Query::Extend(vec![(name.to_string(), expr)], mode)
+    }
+
+    pub fn rename(change: &[(ColumnName, &str)]) -> Self {
+        let change = change
+            .iter()
+            .map(|(col, name)| (col.clone(), name.to_string()));
+        Query::Rename(change.collect())
+    }
+
+    pub fn group(by: &[ColumnName], aggs: &[Agg]) -> Self {
+        Query::Group(by.to_vec(), aggs.to_vec())
+    }
+
+    pub fn nest(by: &[ColumnName], name: &str) -> Self {
+        Query::Nest(by.to_vec(), name.to_string())
+    }
+
+    pub fn unnest(col: ColumnName) -> Self {
+        Query::Unnest(col)
+    }
+
+    pub fn join(kind: Join, on: &[JoinCmp], rhs: Rel) -> Self {
+        Query::Join(kind, on.to_vec(), Rc::new(rhs))
+    }
+
+    pub fn inner(on: &[JoinCmp], rhs: Rel) -> Self {
+        Self::join(Join::Inner, on, rhs)
+    }
+
+    pub fn left(on: &[JoinCmp], rhs: Rel) -> Self {
+        Self::join(Join::Left, on, rhs)
+    }
+
+    pub fn right(on: &[JoinCmp], rhs: Rel) -> Self {
+        Self::join(Join::Right, on, rhs)
+    }
+
+    pub fn full(on: &[JoinCmp], rhs: Rel) -> Self {
+        Self::join(Join::Full, on, rhs)
+    }
+
+    pub fn natural(rhs: Rel) -> Self {
+        Self::join(Join::Natural, &[], rhs)
+    }
+
+    pub fn cross(rhs: Rel) -> Self {
+        Self::join(Join::Cross, &[], rhs)
+    }
+
+    pub fn semi(on: &[JoinCmp], rhs: Rel) -> Self {
+        Self::join(Join::Semi, on, rhs)
+    }
+
+    pub fn anti(on: &[JoinCmp], rhs: Rel) -> Self {
+        Self::join(Join::Anti, on, rhs)
     }
 
     pub fn union(rhs: Rel) -> Self {
@@ -324,12 +1069,23 @@ pub struct ColIter<'a, R> {
 #[derive(Debug)]
 pub struct RowsIter<R> {
     pub pos: usize,
-    pub rel: R,
+    //Shared, so each iterator over the same relation not copy it
+    pub rel: Rc<R>,
+    //The rows, for the relations that can't be read by position like a BTree
+    pub snapshot: Vec<Col>,
 }
 
 impl<R> RowsIter<R> {
     pub fn new(rel: R) -> Self {
-        RowsIter { pos: 0, rel }
+        Self::shared(Rc::new(rel))
+    }
+
+    pub fn shared(rel: Rc<R>) -> Self {
+        RowsIter {
+            pos: 0,
+            rel,
+            snapshot: Vec::new(),
+        }
     }
 }
 
@@ -344,23 +1100,68 @@ pub trait RelIter {
             None
         }
     }
+    /// The bounds of the rows left, like Iterator::size_hint
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+    /// The error that stopped the iteration, if any. The rows before it
+    /// were fine
+    fn error(&self) -> Option<Error> {
+        None
+    }
+    /// Pull all the rows left, or the error that stopped the iteration
+    fn rows_left(&mut self) -> ResultT<Vec<Col>> {
+        let mut rows = Vec::new();
+        while let Some(row) = self.next() {
+            rows.push(row);
+        }
+        match self.error() {
+            Some(e) => Err(e),
+            None => Ok(rows),
+        }
+    }
+    /// As a std Iterator, to use map, zip, collect...
+    fn into_rows(self) -> crate::sequence::IntoRows<Self>
+    where
+        Self: Sized,
+    {
+        crate::sequence::IntoRows::new(self)
+    }
 }
 
-pub fn ref_cell<T>(of: T) -> Rc<RefCell<dyn RelIter>>
-where
-    T: RelIter + 'static,
-{
-    Rc::new(RefCell::new(of))
+impl<T: RelIter + ?Sized> RelIter for Box<T> {
+    fn pos(&self) -> usize {
+        (**self).pos()
+    }
+    fn advance(&mut self) -> bool {
+        (**self).advance()
+    }
+    fn row(&mut self) -> Col {
+        (**self).row()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+    fn error(&self) -> Option<Error> {
+        (**self).error()
+    }
 }
 
+/// Make a iterator that start at the first row, on each call
+pub type IterFactory = Rc<dyn Fn() -> ResultT<Box<dyn RelIter>>>;
+
+/// The clones share the factory, not the rows: each iteration start again,
+/// except if is single pass, like a file
+#[derive(Clone)]
 pub struct Seq {
     pub schema: Schema,
     pub shape: Shape,
-    pub iter: Rc<RefCell<dyn RelIter>>,
+    pub single_pass: bool,
+    pub(crate) make: IterFactory,
 }
 
 pub trait Relation: Debug {
-    //fn schema(&self) -> Rc<Schema>;
+    fn schema(&self) -> Schema;
 
     fn shape(&self) -> Shape;
 
@@ -378,15 +1179,69 @@ pub trait Relation: Debug {
 
     fn as_seq(&self) -> Seq;
 
-    fn filter(&self, cmp: CmOp) -> Rel;
+    fn filter(&self, pred: Predicate) -> ResultT<Rel>;
+
+    /// All the rows of both, keeping the duplicates like UNION ALL. For a
+    /// union without duplicates follow it with distinct
+    fn union(&self, other: &Rel) -> ResultT<Rel>;
+    fn diff(&self, other: &Rel) -> ResultT<Rel>;
+    fn intersect(&self, other: &Rel) -> ResultT<Rel>;
+
+    /// Stable sort by the keys, in order of priority
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel>;
+
+    /// Skip the first rows, then return at most `take` rows
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel>;
+
+    /// Keep the first row for each distinct value of the columns, or of the
+    /// whole row if `on` is empty
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel>;
+
+    /// Join the rows where all the `on` comparisons hold. The result is always a table
+    fn join(&self, other: &Rel, kind: Join, on: &[JoinCmp]) -> ResultT<Rel> {
+        let lhs = crate::join::Side::new(self)?;
+        let rhs = crate::join::Side::new(other)?;
+
+        Ok(crate::join::join(&lhs, &rhs, kind, on)?.into())
+    }
+
+    /// One row per distinct value of the `by` columns, followed by the aggregates
+    fn group(&self, by: &[ColumnName], aggs: &[Agg]) -> ResultT<Rel> {
+        Ok(crate::group::group(self, by, aggs)?.into())
+    }
+
+    /// One row per distinct value of the `by` columns, with the rest of the
+    /// columns of the group as a relation in the column `name`
+    fn nest(&self, by: &[ColumnName], name: &str) -> ResultT<Rel> {
+        Ok(crate::nest::nest(self, by, name)?.into())
+    }
+
+    /// Flatten back a nested relation column into his columns, a row per nested row
+    fn unnest(&self, col: &ColumnName) -> ResultT<Rel> {
+        Ok(crate::nest::unnest(self, col)?.into())
+    }
 
-    fn union(&self, other: &Rel) -> Rel;
-    fn diff(&self, other: &Rel) -> Rel;
-    fn intersect(&self, other: &Rel) -> Rel;
+    /// Keep only the columns, in the order given
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel>;
 
-    //fn join(&self, cmp: CmOp) -> Self;
+    /// Remove the columns, keeping the others in their order
+    fn deselect(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        let schema = self.schema();
+        let keep = schema.except(&schema.resolve_pos_many(cols)?);
+        let cols: Vec<_> = keep.into_iter().map(ColumnName::Pos).collect();
+        self.project(&cols)
+    }
+
+    /// Add computed columns at the end. `mode` decide what a overflow or
+    /// divide by zero in the expressions return
+    fn extend(&self, cols: &[(String, RowExpr)], mode: OnError) -> ResultT<Rel> {
+        let (schema, exprs) = crate::relational::extend_schema(&self.schema(), cols)?;
+        let data = crate::relational::to_rows(self)?
+            .into_iter()
+            .map(|row| crate::relational::extend_row(&exprs, row, mode));
+
+        Ok(Table::new(schema, data.collect::<ResultT<_>>()?).into())
+    }
 
-    //fn project(&self, cmp: CmOp) -> Self;
-    //fn extend(&self, cmp: CmOp) -> Self;
-    //fn rename(&self, cmp: CmOp) -> Self;
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel>;
 }
@@ -1,9 +1,13 @@
-use std::collections::HashSet;
-
 use crate::dsl::*;
+use crate::error::*;
+use crate::relational::*;
 use crate::types::*;
 
 impl Relation for Vector {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+
     fn shape(&self) -> Shape {
         Shape::Vector(self.data.len())
     }
@@ -16,64 +20,95 @@ impl Relation for Vector {
     }
 
     fn as_seq(&self) -> Seq {
-        Seq::new(self.schema.clone(), &self.shape(), ref_cell(self.rows()))
+        Seq::of_rows(self.schema.clone(), &self.shape(), self.clone())
     }
 
-    fn filter(&self, cmp: CmOp) -> Rel {
-        let apply = cmp.get_fn();
-        let data = self.data.iter().filter(|x| apply(x, &cmp.rhs)).cloned();
+    fn filter(&self, pred: Predicate) -> ResultT<Rel> {
+        pred.check(1)?;
+        let data = self
+            .data
+            .iter()
+            .filter(|x| pred.eval(std::slice::from_ref(x)))
+            .cloned();
         let rel = Self::new(self.schema.clone(), data.collect());
-        rel.into()
-    }
-
-    fn union(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::One(b) => {
-                let mut data = self.data.clone();
-                data.push(b.clone());
-
-                Self::new(self.schema.clone(), data).into()
-            }
-            Rel::Vector(b) => {
-                let data = self.data.iter().chain(b.data.iter()).cloned();
-                Self::new(self.schema.clone(), data.collect()).into()
-            }
-            _ => unimplemented!(),
-        }
+        Ok(rel.into())
     }
 
-    fn diff(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::Vector(b) => {
-                let a = self.as_set();
-                let b = b.as_set();
+    fn union(&self, other: &Rel) -> ResultT<Rel> {
+        let data = union_rows(&self.as_rows(), aligned_rows(&self.schema, other)?);
+        Ok(Self::from_rows(self.schema.clone(), data).into())
+    }
 
-                let result = a.difference(&b);
-                Self::new(self.schema.clone(), result.cloned().collect()).into()
-            }
-            _ => unimplemented!(),
-        }
+    fn diff(&self, other: &Rel) -> ResultT<Rel> {
+        let data = diff_rows(&self.as_rows(), aligned_rows(&self.schema, other)?);
+        Ok(Self::from_rows(self.schema.clone(), data).into())
+    }
+
+    fn intersect(&self, other: &Rel) -> ResultT<Rel> {
+        let data = intersect_rows(&self.as_rows(), aligned_rows(&self.schema, other)?);
+        Ok(Self::from_rows(self.schema.clone(), data).into())
+    }
+
+    fn sort(&self, keys: &[SortKey]) -> ResultT<Rel> {
+        let keys = sort_positions(&self.schema, keys)?;
+        let mut data = self.data.clone();
+        data.sort_by(|a, b| compare_rows(&keys, std::slice::from_ref(a), std::slice::from_ref(b)));
+
+        Ok(Self::new(self.schema.clone(), data).into())
     }
 
-    fn intersect(&self, other: &Rel) -> Rel {
-        match other {
-            Rel::Vector(b) => {
-                let a = self.as_set();
-                let b = b.as_set();
+    fn limit(&self, skip: usize, take: usize) -> ResultT<Rel> {
+        let data = limit_rows(&self.data, skip, take);
+        Ok(Self::new(self.schema.clone(), data).into())
+    }
+
+    fn distinct(&self, on: &[ColumnName]) -> ResultT<Rel> {
+        distinct_positions(&self.schema, on)?;
+        let data = distinct_rows(&self.data, |x| vec![x.clone()]);
+
+        Ok(Self::new(self.schema.clone(), data).into())
+    }
 
-                let result = a.intersection(&b);
-                Self::new(self.schema.clone(), result.cloned().collect()).into()
-            }
-            _ => unimplemented!(),
+    fn project(&self, cols: &[ColumnName]) -> ResultT<Rel> {
+        let pos = self.schema.resolve_pos_many(cols)?;
+        if pos == [0] {
+            return Ok(self.clone().into());
         }
+        let schema = self.schema.only(&pos);
+        let data = self.data.iter().map(|x| vec![x.clone(); pos.len()]);
+
+        Ok(Table::new(schema, data.collect()).into())
+    }
+
+    fn rename(&self, change: &[(ColumnName, String)]) -> ResultT<Rel> {
+        let schema = rename_schema(&self.schema, change)?;
+        Ok(Self::new(schema, self.data.clone()).into())
     }
 }
 
 impl Vector {
+    /// Unchecked: the values are not validated against the schema, not even a
+    /// not-null column. The operations use it for values that already conform,
+    /// use try_new for the data that come from outside
     pub fn new(schema: Schema, data: Vec<Scalar>) -> Self {
         Vector { schema, data }
     }
 
+    /// Like new, but fail on the first value that not conform to the schema
+    pub fn try_new(schema: Schema, data: Vec<Scalar>) -> ResultT<Self> {
+        Self::conform(schema, data, false)
+    }
+
+    /// Like try_new, casting up the numbers to the type of the column
+    pub fn try_cast(schema: Schema, data: Vec<Scalar>) -> ResultT<Self> {
+        Self::conform(schema, data, true)
+    }
+
+    fn conform(schema: Schema, data: Vec<Scalar>, implicit: bool) -> ResultT<Self> {
+        let rows = schema.conform_rows(to_columns(data), implicit)?;
+        Ok(Self::from_rows(schema, rows))
+    }
+
     pub fn empty(kind: DataType) -> Self {
         let schema = schema_it(kind);
         let data = vec![];
@@ -86,7 +121,7 @@ impl Vector {
     }
 
     pub fn new_scalars(data: &[Scalar]) -> Self {
-        let kind = data[0].kind();
+        let kind = infer_type(data);
         let schema = schema_it(kind);
         Vector {
             schema,
@@ -94,8 +129,13 @@ impl Vector {
         }
     }
 
-    fn as_set(&self) -> HashSet<Scalar> {
-        self.data.iter().cloned().collect()
+    pub fn from_rows(schema: Schema, rows: Vec<Col>) -> Self {
+        let data = rows.into_iter().map(|mut x| x.swap_remove(0));
+        Self::new(schema, data.collect())
+    }
+
+    fn as_rows(&self) -> Vec<Col> {
+        to_columns(self.data.clone())
     }
 }
 
@@ -114,4 +154,9 @@ impl RelIter for RowsIter<Vector> {
         let pos = self.pos - 1;
         vec![self.rel.data[pos].clone()]
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.rel.data.len().saturating_sub(self.pos);
+        (left, Some(left))
+    }
 }
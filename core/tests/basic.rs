@@ -1,3 +1,6 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use tablam_core::dsl::*;
 use tablam_core::types::DataType::*;
 use tablam_core::types::*;
@@ -7,7 +10,7 @@ use crate::common::*;
 
 #[test]
 fn test_mem_size() {
-    assert_eq!(std::mem::size_of::<Scalar>(), 32);
+    assert_eq!(std::mem::size_of::<Scalar>(), 24);
 }
 
 #[test]
@@ -29,3 +32,65 @@ fn test_create() {
     //    let table1 = table_1();
     //    check_schema(&table1, 3, 3);
 }
+
+#[test]
+fn test_schema_order() {
+    let a = Schema::new(vec![field("a", I64), field("b", UTF8).not_null()]);
+    let b = Schema::new(vec![field("b", UTF8), field("a", I64)]);
+    assert_eq!(a, b);
+    assert_eq!(a.cmp(&b), std::cmp::Ordering::Equal);
+
+    let c = Schema::new(vec![field("a", I64), field("c", UTF8)]);
+    assert!(a < c);
+    assert!(b < c);
+}
+
+#[test]
+fn test_complex_types() {
+    let flags = bits(&[true, false, true]);
+    let data = blob(&[0, 15, 255]);
+    let some = sum("Some", int(1));
+    let none = sum("None", Scalar::None);
+    let pair = product(&[int(1), "a".into()]);
+
+    assert_eq!(flags.kind(), BitVec);
+    assert_eq!(data.kind(), Blob);
+    assert_eq!(some.kind(), Sum(vec![("Some".into(), I32)]));
+    assert_eq!(pair.kind(), Product(vec![I32, UTF8]));
+    assert_eq!(format!("{}", pair.kind()), "Product(I32, UTF8)");
+
+    //A field of a sum take any of his alternatives
+    let option = Sum(vec![("Some".into(), I32), ("None".into(), None)]);
+    let field = Field::new("x", option);
+    assert_eq!(format!("{}", field.kind), "Sum(Some:I32 | None:None)");
+    assert!(field.conform(some.clone(), 0, false).is_ok());
+    assert!(field.conform(none.clone(), 0, false).is_ok());
+    assert!(field.conform(sum("Ok", int(1)), 0, false).is_err());
+    assert!(field.conform(sum("Some", "a".into()), 0, false).is_err());
+    let field = Field::new("x", Product(vec![I32, UTF8]));
+    assert!(field
+        .conform(product(&[int(2), Scalar::None]), 0, false)
+        .is_ok());
+    assert!(field.conform(product(&[int(2)]), 0, false).is_err());
+
+    assert_eq!(format!("{}", flags), "0b101");
+    assert_eq!(format!("{}", data), "0x000fff");
+    assert_eq!(format!("{}", some), "Some(1)");
+    assert_eq!(format!("{}", none), "None");
+    assert_eq!(format!("{}", pair), "(1, a)");
+
+    //Ordered first by type, then by value
+    assert!(int(1) < flags);
+    assert!(flags < data);
+    assert!(none < some);
+    assert!(sum("Some", int(1)) < sum("Some", int(2)));
+    assert!(some < pair);
+
+    let hash = |x: &Scalar| {
+        let mut state = DefaultHasher::new();
+        x.hash(&mut state);
+        state.finish()
+    };
+    assert_eq!(hash(&some), hash(&sum("Some", int(1))));
+    assert_ne!(hash(&some), hash(&none));
+}
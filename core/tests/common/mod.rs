@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use tablam_core::dsl::*;
 use tablam_core::types::*;
 
@@ -25,6 +27,26 @@ pub fn rel_nums3() -> Vector {
     array(nums_3().as_slice())
 }
 
+pub fn table_1() -> Table {
+    let schema = schema(&[("id", DataType::I64), ("name", DataType::UTF8)]);
+    let data = vec![
+        vec![int64(2), str("b")],
+        vec![int64(1), none()],
+        vec![int64(3), str("a")],
+    ];
+    Table::new(schema, data)
+}
+
+pub fn table_2() -> Table {
+    let schema = schema(&[("id", DataType::I64), ("customer", DataType::I64)]);
+    let data = vec![
+        vec![int64(10), int64(1)],
+        vec![int64(11), int64(1)],
+        vec![int64(12), int64(4)],
+    ];
+    Table::new(schema, data)
+}
+
 pub fn rel_empty() -> Vector {
     array_empty(DataType::I32)
 }
@@ -53,10 +75,10 @@ where
     let rel: Rel = table.into();
     let result = result.into();
 
-    let rel = rel.query(&[query]);
+    let rel = rel.query(&[query]).unwrap();
 
-    if let Rel::Seq(mut x) = rel {
-        assert_eq!(x.materialize(), result);
+    if let Rel::Seq(x) = rel {
+        assert_eq!(x.materialize().unwrap(), result);
     } else {
         assert_eq!(rel, result);
     }
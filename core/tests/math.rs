@@ -0,0 +1,208 @@
+use rust_decimal::Decimal;
+
+use tablam_core::dsl::*;
+use tablam_core::error::*;
+use tablam_core::stdlib::math::*;
+use tablam_core::types::*;
+
+#[test]
+fn test_promote() {
+    assert_eq!(math_add(&int(1), &int(2)), Ok(int(3)));
+    assert_eq!(math_add(&int(1), &int64(2)), Ok(int64(3)));
+    assert_eq!(math_add(&int(1), &value(2isize)), Ok(value(3isize)));
+    assert_eq!(math_add(&value(1isize), &int64(2)), Ok(int64(3)));
+    assert_eq!(math_add(&int(1), &float(2.5)), Ok(float(3.5)));
+    assert_eq!(math_mul(&int64(2), &dec(125, 2)), Ok(dec(250, 2)));
+    assert_eq!(math_minus(&float(2.5), &int64(1)), Ok(float(1.5)));
+    assert_eq!(math_div(&float(7.0), &float(2.0)), Ok(float(3.5)));
+}
+
+#[test]
+fn test_promote_decimal_float() {
+    assert_eq!(math_add(&dec(15, 1), &float(2.25)), Ok(dec(375, 2)));
+    assert_eq!(math_add(&float(2.25), &dec(15, 1)), Ok(dec(375, 2)));
+    assert_eq!(
+        promote_kind(DataType::F64, DataType::Decimal),
+        Ok(DataType::Decimal)
+    );
+}
+
+#[test]
+fn test_null() {
+    assert_eq!(math_add(&none(), &int(1)), Ok(none()));
+    assert_eq!(math_div(&float(1.5), &none()), Ok(none()));
+    assert_eq!(math_add(&none(), &none()), Ok(none()));
+    //The null not hide a type error
+    assert_eq!(
+        math_mul(&none(), &str("a")),
+        Err(Error::TypeMismatch(DataType::None, DataType::UTF8))
+    );
+    assert_eq!(
+        math_bit_and(&bool(true), &none()),
+        Err(Error::TypeMismatch(DataType::Bool, DataType::None))
+    );
+}
+
+#[test]
+fn test_mismatch() {
+    assert_eq!(
+        math_add(&int(1), &str("a")),
+        Err(Error::TypeMismatch(DataType::I32, DataType::UTF8))
+    );
+    assert_eq!(
+        math_add(&bool(true), &bool(false)),
+        Err(Error::TypeMismatch(DataType::Bool, DataType::Bool))
+    );
+    assert_eq!(
+        cast_num(&int64(1), DataType::I32),
+        Err(Error::InvalidCast(DataType::I64, DataType::I32))
+    );
+}
+
+#[test]
+fn test_checked() {
+    let max = int64(i64::MAX);
+    assert_eq!(
+        math_add(&max, &int64(1)),
+        Err(Error::Overflow(DataType::I64))
+    );
+    assert_eq!(
+        math_mul(&int(i32::MIN), &int(2)),
+        Err(Error::Overflow(DataType::I32))
+    );
+    assert_eq!(
+        math_div(&int64(i64::MIN), &int64(-1)),
+        Err(Error::Overflow(DataType::I64))
+    );
+    assert_eq!(math_div(&int(1), &int(0)), Err(Error::DivideByZero));
+    assert_eq!(math_div(&dec(1, 0), &dec(0, 2)), Err(Error::DivideByZero));
+    assert_eq!(math_div(&float(1.0), &float(0.0)), Err(Error::DivideByZero));
+    assert_eq!(
+        math_mul(&float(f64::MAX), &float(2.0)),
+        Err(Error::Overflow(DataType::F64))
+    );
+    assert_eq!(
+        math_add(&value(Decimal::max_value()), &dec(1, 0)),
+        Err(Error::Overflow(DataType::Decimal))
+    );
+}
+
+#[test]
+fn test_on_error() {
+    let max = int64(i64::MAX);
+    let sat = OnError::Saturate;
+    assert_eq!(math_add_with(&max, &int64(1), sat), Ok(max.clone()));
+    assert_eq!(
+        math_minus_with(&int(i32::MIN), &int(1), sat),
+        Ok(int(i32::MIN))
+    );
+    assert_eq!(
+        math_div_with(&int(1), &int(0), sat),
+        Err(Error::DivideByZero)
+    );
+    assert_eq!(
+        BinOp::Mul.eval(&float(f64::MAX), &float(-2.0), sat),
+        Ok(float(f64::MIN))
+    );
+
+    let null = OnError::Null;
+    assert_eq!(math_add_with(&max, &int64(1), null), Ok(none()));
+    assert_eq!(BinOp::Div.eval(&int(1), &int(0), null), Ok(none()));
+    assert_eq!(BinOp::Add.eval(&int(1), &int(2), null), Ok(int(3)));
+}
+
+#[test]
+fn test_extended() {
+    assert_eq!(math_mod(&int(-7), &int(2)), Ok(int(-1)));
+    assert_eq!(math_mod(&int(i32::MIN), &int(-1)), Ok(int(0)));
+    assert_eq!(math_mod(&dec(75, 1), &int(2)), Ok(dec(15, 1)));
+    assert_eq!(math_int_div(&int(-7), &int(2)), Ok(int(-3)));
+    assert_eq!(math_int_div(&dec(75, 1), &dec(2, 0)), Ok(dec(3, 0)));
+    assert_eq!(math_int_div(&float(7.5), &int(2)), Ok(float(3.0)));
+    assert_eq!(math_mod(&int(1), &int(0)), Err(Error::DivideByZero));
+
+    assert_eq!(math_pow(&int(3), &int(4)), Ok(int(81)));
+    assert_eq!(math_pow(&dec(15, 1), &dec(2, 0)), Ok(dec(225, 2)));
+    assert_eq!(math_pow(&dec(2, 0), &int(-2)), Ok(dec(25, 2)));
+    assert_eq!(math_pow(&float(4.0), &float(0.5)), Ok(float(2.0)));
+    assert_eq!(
+        math_pow(&int(2), &int(40)),
+        Err(Error::Overflow(DataType::I32))
+    );
+    assert!(matches!(math_pow(&int(2), &int(-1)), Err(Error::Domain(_))));
+    assert!(matches!(
+        math_pow(&float(-8.0), &float(0.5)),
+        Err(Error::Domain(_))
+    ));
+}
+
+#[test]
+fn test_bitwise() {
+    assert_eq!(math_bit_and(&int(6), &int(3)), Ok(int(2)));
+    assert_eq!(math_bit_or(&int(6), &int(3)), Ok(int(7)));
+    assert_eq!(math_bit_xor(&int(6), &int64(3)), Ok(int64(5)));
+    assert_eq!(math_shl(&int(1), &int(4)), Ok(int(16)));
+    assert_eq!(math_shr(&int(16), &int(4)), Ok(int(1)));
+    assert_eq!(
+        math_shl(&int(1), &int(32)),
+        Err(Error::Overflow(DataType::I32))
+    );
+    assert_eq!(
+        math_bit_and(&float(1.0), &int(1)),
+        Err(Error::Unsupported("bitwise and".into(), DataType::F64))
+    );
+}
+
+#[test]
+fn test_unary() {
+    let fail = OnError::Fail;
+    assert_eq!(UnaryOp::Neg.eval(&int(2), fail), Ok(int(-2)));
+    assert_eq!(UnaryOp::Neg.eval(&dec(25, 1), fail), Ok(dec(-25, 1)));
+    assert_eq!(UnaryOp::Abs.eval(&float(-2.5), fail), Ok(float(2.5)));
+    assert_eq!(UnaryOp::Abs.eval(&none(), fail), Ok(none()));
+    assert_eq!(
+        UnaryOp::Abs.eval(&int(i32::MIN), fail),
+        Err(Error::Overflow(DataType::I32))
+    );
+    assert_eq!(
+        UnaryOp::Abs.eval(&int(i32::MIN), OnError::Saturate),
+        Ok(int(i32::MAX))
+    );
+    assert!(matches!(
+        UnaryOp::Neg.eval(&str("a"), fail),
+        Err(Error::Unsupported(_, DataType::UTF8))
+    ));
+
+    assert_eq!(UnaryOp::Sqrt.eval(&int(9), fail), Ok(float(3.0)));
+    assert_eq!(UnaryOp::Log.eval(&float(1.0), fail), Ok(float(0.0)));
+    assert_eq!(UnaryOp::Exp.eval(&int(0), fail), Ok(float(1.0)));
+    assert_eq!(UnaryOp::Sqrt.eval(&dec(225, 2), fail), Ok(float(1.5)));
+    assert_eq!(UnaryOp::Log.eval(&dec(1, 0), fail), Ok(float(0.0)));
+    assert_eq!(UnaryOp::Exp.eval(&dec(0, 0), fail), Ok(float(1.0)));
+    assert!(matches!(
+        UnaryOp::Sqrt.eval(&int(-1), fail),
+        Err(Error::Domain(_))
+    ));
+    assert_eq!(UnaryOp::Log.eval(&int(0), OnError::Null), Ok(none()));
+    assert_eq!(
+        UnaryOp::Exp.eval(&float(1000.0), fail),
+        Err(Error::Overflow(DataType::F64))
+    );
+}
+
+#[test]
+fn test_rounding() {
+    let fail = OnError::Fail;
+    let money = dec(-12345, 3);
+    assert_eq!(UnaryOp::Round(2).eval(&money, fail), Ok(dec(-1235, 2)));
+    assert_eq!(UnaryOp::Floor(2).eval(&money, fail), Ok(dec(-1235, 2)));
+    assert_eq!(UnaryOp::Ceil(2).eval(&money, fail), Ok(dec(-1234, 2)));
+    assert_eq!(UnaryOp::Trunc(2).eval(&money, fail), Ok(dec(-1234, 2)));
+    assert_eq!(UnaryOp::Trunc(0).eval(&money, fail), Ok(dec(-12, 0)));
+    assert_eq!(UnaryOp::Round(5).eval(&money, fail), Ok(money));
+
+    assert_eq!(UnaryOp::Round(1).eval(&float(2.25), fail), Ok(float(2.3)));
+    assert_eq!(UnaryOp::Floor(0).eval(&float(-2.5), fail), Ok(float(-3.0)));
+    assert_eq!(UnaryOp::Ceil(1).eval(&float(2.21), fail), Ok(float(2.3)));
+    assert_eq!(UnaryOp::Round(2).eval(&int(7), fail), Ok(int(7)));
+}
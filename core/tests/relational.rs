@@ -1,13 +1,40 @@
+use std::cell::Cell;
+use std::convert::TryFrom;
 use std::rc::Rc;
 
+use rust_decimal::Decimal;
+
 use tablam_core::dsl::*;
+use tablam_core::error::*;
+use tablam_core::relational::to_rows;
+use tablam_core::sequence::IterRel;
+use tablam_core::stdlib::file::IoFile;
 use tablam_core::types::*;
 
 mod common;
 use crate::common::*;
 
 #[test]
-fn test_project() {}
+fn test_project() {
+    let t1 = table_1();
+    let names = schema_single("name", DataType::UTF8);
+    let data = t1.data.iter().map(|x| vec![x[1].clone()]).collect();
+    let result = Table::new(names, data);
+
+    check_query(t1.clone(), Query::select(&[coln("name")]), result.clone());
+    check_query(t1.clone(), Query::deselect(&[colp(0)]), result);
+
+    let swap = Query::select(&[colp(1), colp(0)]);
+    let rel: Rel = t1.into();
+    assert_eq!(
+        rel.query(&[swap]).unwrap().schema().as_slice(),
+        vec!["name", "id"]
+    );
+
+    let v1 = rel_nums1();
+    check_query(v1.clone(), Query::select(&[coln("it")]), v1.clone());
+    check_query(v1.as_seq(), Query::select(&[colp(0)]), v1);
+}
 
 #[test]
 fn test_where() {
@@ -21,7 +48,917 @@ fn test_where() {
 
     let v1 = rel_nums1();
     check_query(v1.clone(), cmp.clone(), array(&[1i64]));
-    check_query(v1, fail.clone(), empty);
+    check_query(v1.clone(), fail.clone(), empty.clone());
+    check_query(v1.as_seq(), cmp, array(&[1i64]));
+    check_query(v1.as_seq(), fail, empty);
+    check_query(
+        v1.as_seq(),
+        Query::greater(0, int64(1)),
+        array(&[2i64, 3i64]),
+    );
+
+    //The numbers of other type are compared as the larger type
+    check_query(v1.clone(), Query::eq(0, int(1)), array(&[1i64]));
+    check_query(
+        v1.as_seq(),
+        Query::less(0, dec(25, 1)),
+        array(&[1i64, 2i64]),
+    );
+    let sql = Predicate::three_valued(CmOp::greater_eq(0, int(2).into()).into());
+    check_query(v1, Query::filter(sql), array(&[2i64, 3i64]));
+
+    let t1 = Table::new(
+        schema(&[("a", DataType::I64), ("b", DataType::Decimal)]),
+        vec![vec![int64(1), dec(1, 0)], vec![int64(2), dec(25, 1)]],
+    );
+    let first = Table::new(t1.schema.clone(), vec![t1.data[0].clone()]);
+    check_query(t1.clone(), Query::eq(0, int(1)), first.clone());
+    let same = Predicate::cols(CompareOp::Eq, 0, 1);
+    check_query(t1.clone(), Query::filter(same.clone()), first.clone());
+    check_query(t1, Query::filter(Predicate::three_valued(same)), first);
+}
+
+#[test]
+fn test_where_logic() {
+    let t1 = Table::new(
+        schema(&[
+            ("a", DataType::I64),
+            ("b", DataType::UTF8),
+            ("c", DataType::Bool),
+        ]),
+        vec![
+            vec![int64(1), str("x"), bool(true)],
+            vec![int64(2), str("x"), bool(false)],
+            vec![int64(3), str("y"), bool(false)],
+            vec![int64(2), str("y"), bool(true)],
+        ],
+    );
+    // a > 1 and (b = "x" or not c)
+    let pred = Predicate::and(vec![
+        CmOp::greater(0, rvalue(1i64)).into(),
+        Predicate::or(vec![
+            CmOp::eq(1, rvalue("x".to_string())).into(),
+            Predicate::negate(Predicate::is_true(2)),
+        ]),
+    ]);
+    let result = Table::new(t1.schema.clone(), t1.data[1..3].to_vec());
+    check_query(t1.clone(), Query::filter(pred.clone()), result.clone());
+
+    let rel: Rel = t1.as_seq().into();
+    assert_eq!(
+        to_rows(&rel.query(&[Query::filter(pred)]).unwrap()).unwrap(),
+        result.data
+    );
+
+    let prices = Table::new(
+        schema(&[("price", DataType::I64), ("cost", DataType::I64)]),
+        vec![vec![int64(10), int64(5)], vec![int64(4), int64(5)]],
+    );
+    let result = Table::new(prices.schema.clone(), prices.data[..1].to_vec());
+    let pred = Predicate::cols(CompareOp::Greater, 0, 1);
+    check_query(prices, Query::filter(pred), result);
+
+    let r1 = Range::new(0, 10, 1);
+    let pred = Predicate::and(vec![
+        CmOp::greater(0, rvalue(2isize)).into(),
+        CmOp::less(0, rvalue(5isize)).into(),
+    ]);
+    check_query(r1.clone(), Query::filter(pred), Range::new(3, 5, 1));
+
+    let pred = Predicate::or(vec![
+        CmOp::eq(0, rvalue(2isize)).into(),
+        CmOp::eq(0, rvalue(5isize)).into(),
+    ]);
+    check_query(r1, Query::filter(pred), array(&[2isize, 5]));
+}
+
+#[test]
+fn test_nulls() {
+    let t1 = table_1();
+    let rows = |of: Vec<Col>| Table::new(t1.schema.clone(), of);
+    let null_row = || vec![int64(1), none()];
+
+    check_query(t1.clone(), Query::is_null(1), rows(vec![null_row()]));
+    check_query(
+        t1.clone(),
+        Query::not_null(1),
+        rows(vec![vec![int64(2), str("b")], vec![int64(3), str("a")]]),
+    );
+
+    //By default null is a value, equal to itself
+    let is_none = CmOp::eq(1, none().into());
+    check_query(
+        t1.clone(),
+        Query::filter(is_none.clone().into()),
+        rows(vec![null_row()]),
+    );
+    let sql = Predicate::three_valued(is_none.into());
+    check_query(t1.clone(), Query::filter(sql), rows(vec![]));
+
+    //not (name = "a") is unknown for the null
+    let not_a = Predicate::negate(CmOp::eq(1, str("a").into()).into());
+    check_query(
+        t1.clone(),
+        Query::filter(not_a.clone()),
+        rows(vec![vec![int64(2), str("b")], null_row()]),
+    );
+    let sql = Predicate::three_valued(not_a);
+    check_query(
+        t1.clone(),
+        Query::filter(sql),
+        rows(vec![vec![int64(2), str("b")]]),
+    );
+
+    //unknown or true is true
+    let either = Predicate::or(vec![
+        CmOp::eq(1, str("a").into()).into(),
+        CmOp::eq(0, int64(1).into()).into(),
+    ]);
+    let sql = Predicate::three_valued(either);
+    check_query(
+        t1.clone(),
+        Query::filter(sql),
+        rows(vec![null_row(), vec![int64(3), str("a")]]),
+    );
+
+    let name = RowExpr::coalesce(&[RowExpr::col(coln("name")), RowExpr::value(str("?"))]);
+    let result = t1.extend(&[("label".into(), name)], OnError::Fail).unwrap();
+    let labels: Vec<_> = to_rows(&result)
+        .unwrap()
+        .into_iter()
+        .map(|x| x[2].clone())
+        .collect();
+    assert_eq!(labels, vec![str("b"), str("?"), str("a")]);
+    assert_eq!(Scalar::coalesce(&[none(), int64(1), int64(2)]), int64(1));
+}
+
+#[test]
+fn test_not_nullable() {
+    let fields = vec![
+        field("id", DataType::I64).not_null(),
+        field("name", DataType::UTF8),
+    ];
+    let data = table_1().data;
+    assert!(Table::try_new(Schema::new(fields.clone()), data.clone()).is_ok());
+
+    let fields = vec![fields[0].clone(), fields[1].clone().not_null()];
+    let result = Table::try_new(Schema::new(fields.clone()), data);
+    assert_eq!(result, Err(Error::NullNotAllowed("name".into(), 1)));
+
+    let result = Vector::try_new(Schema::new(vec![fields[1].clone()]), vec![str("a"), none()]);
+    assert_eq!(result, Err(Error::NullNotAllowed("name".into(), 1)));
+
+    //The side of a outer join that can be filled with nulls become nullable
+    let t2 = Table::new(Schema::new(vec![fields[0].clone()]), vec![vec![int64(7)]]);
+    let on = [JoinCmp::eq(coln("id"), coln("id"))];
+    let joined = t2.join(&table_1().into(), Join::Left, &on).unwrap();
+    let nullable: Vec<_> = joined.schema().columns.iter().map(|x| x.nullable).collect();
+    assert_eq!(nullable, vec![false, true, true]);
+}
+
+#[test]
+fn test_conform() {
+    let t1 = table_1();
+    assert_eq!(
+        Table::try_new(t1.schema.clone(), t1.data.clone()),
+        Ok(t1.clone())
+    );
+
+    let short = vec![vec![int64(2), str("b")], vec![int64(1)]];
+    let result = Table::try_new(t1.schema.clone(), short);
+    assert_eq!(result, Err(Error::ArityMismatch(1, 2, 1)));
+
+    let wrong = vec![vec![int64(2), str("b")], vec![str("c"), str("d")]];
+    let result = Table::try_new(t1.schema.clone(), wrong.clone());
+    let cause = Error::InvalidCast(DataType::UTF8, DataType::I64);
+    assert_eq!(
+        result,
+        Err(Error::InvalidValue(1, "id".into(), Box::new(cause)))
+    );
+    assert!(Table::try_cast(t1.schema.clone(), wrong).is_err());
+
+    //Only with implicit casting a I32 fit in a I64 column
+    let small = vec![vec![int(2), str("b")]];
+    assert!(Table::try_new(t1.schema.clone(), small.clone()).is_err());
+    let result = Table::try_cast(t1.schema.clone(), small).unwrap();
+    assert_eq!(result.data, vec![vec![int64(2), str("b")]]);
+
+    let nums = schema_it(DataType::Decimal);
+    let result = Vector::try_cast(nums.clone(), vec![int(1), int64(2)]).unwrap();
+    assert_eq!(result.data, vec![dec(1, 0), dec(2, 0)]);
+    let result = Vector::try_cast(nums, vec![int(1), str("x")]);
+    let cause = Error::InvalidCast(DataType::UTF8, DataType::Decimal);
+    assert_eq!(
+        result,
+        Err(Error::InvalidValue(1, "it".into(), Box::new(cause)))
+    );
+}
+
+#[test]
+fn test_shapes() {
+    let rels: Vec<Rel> = vec![
+        int64(1).into(),
+        row(&[int64(1), str("a")]).into(),
+        rel_nums1().into(),
+        kv([DataType::I64, DataType::UTF8], &[(int64(1), str("a"))]).into(),
+        Range::new(0, 4, 2).into(),
+        table_1().into(),
+        table_1().as_seq().into(),
+    ];
+    let shapes: Vec<_> = rels.iter().map(|x| x.shape()).collect();
+    assert_eq!(
+        shapes,
+        vec![
+            Shape::Scalar,
+            Shape::Row(2),
+            Shape::Vector(3),
+            Shape::KV(1),
+            Shape::Vector(2),
+            Shape::Table(2, 3),
+            Shape::Table(2, 3),
+        ]
+    );
+
+    //A empty result keep the columns of his schema
+    let none = table_1().filter(Predicate::is_null(0)).unwrap();
+    assert_eq!(none.shape(), Shape::Table(2, 0));
+}
+
+#[test]
+fn test_kv() {
+    let types = [DataType::I64, DataType::UTF8];
+    let pairs = [
+        (int64(2), str("b")),
+        (int64(1), str("a")),
+        (int64(2), str("c")),
+    ];
+    let map = kv(types.clone(), &pairs);
+    let sorted = kv(types.clone(), &[(int64(1), str("a")), (int64(2), str("c"))]);
+    assert_eq!(map, sorted);
+    assert_eq!(
+        to_rows(&map).unwrap(),
+        vec![vec![int64(1), str("a")], vec![int64(2), str("c")]]
+    );
+    assert_eq!(
+        format!("{}", Rel::from(map.clone())),
+        "[key:I64, value:UTF8; 1, a; 2, c]"
+    );
+
+    let one = kv(types.clone(), &[(int64(2), str("c"))]);
+    check_query(map.clone(), Query::eq(0, int64(2)), one.clone());
+    check_query(map.clone(), Query::limit(1, 5), one.clone());
+    check_query(
+        map.clone(),
+        Query::diff(kv(types.clone(), &[(int64(1), str("a"))]).into()),
+        one.clone(),
+    );
+    check_query(
+        map.clone(),
+        Query::intersection(one.clone().into()),
+        one.clone(),
+    );
+
+    //A union can repeat the keys, so is a table
+    let both = Table::new(
+        schema_kv(types.clone()),
+        vec![vec![int64(2), str("c")], vec![int64(2), str("c")]],
+    );
+    check_query(one.clone(), Query::union(one.into()), both);
+
+    //The rows are by position, even if not read
+    let mut rows = map.rows();
+    assert!(rows.advance());
+    assert_eq!(rows.next(), Some(vec![int64(2), str("c")]));
+    assert_eq!(rows.next(), None);
+
+    //Sorted by the value, the rows are not longer in key order
+    let by_value = Query::sort(&[SortKey::desc(colp(1))]);
+    let sorted = Table::new(
+        schema_kv(types.clone()),
+        vec![vec![int64(2), str("c")], vec![int64(1), str("a")]],
+    );
+    check_query(map.clone(), by_value.clone(), sorted.clone());
+    check_query(map.as_seq(), by_value, sorted);
+
+    let wrong = vec![vec![int64(1), str("a")], vec![int64(2)]];
+    let result = BTree::from_rows(schema_kv(types), wrong);
+    assert_eq!(result, Err(Error::ArityMismatch(1, 2, 1)));
+}
+
+#[test]
+fn test_row() {
+    let r = row(&[int64(1), str("a")]);
+    assert_eq!(
+        r.schema,
+        schema(&[("0", DataType::I64), ("1", DataType::UTF8)])
+    );
+    assert_eq!(format!("{}", Rel::from(r.clone())), "[0:I64, 1:UTF8; 1, a]");
+
+    check_query(r.clone(), Query::eq(0, int64(1)), r.clone());
+    check_query(
+        r.clone(),
+        Query::eq(0, int64(2)),
+        Table::new(r.schema.clone(), vec![]),
+    );
+    check_query(
+        r.clone(),
+        Query::select(&[colp(1)]),
+        Row::new(schema_single("1", DataType::UTF8), vec![str("a")]),
+    );
+    check_query(r.clone(), Query::limit(0, 1), r.clone());
+
+    let t = Table::new(
+        r.schema.clone(),
+        vec![r.data.clone(), vec![int64(2), str("b")]],
+    );
+    check_query(
+        r.clone(),
+        Query::union(row(&[int64(2), str("b")]).into()),
+        t.clone(),
+    );
+    check_query(r.clone(), Query::intersection(t.into()), r);
+}
+
+#[test]
+fn test_materialize() {
+    let types = [DataType::I64, DataType::UTF8];
+    let map = kv(types.clone(), &[(int64(1), str("a")), (int64(2), str("c"))]);
+    let one = kv(types.clone(), &[(int64(2), str("c"))]);
+    check_query(map.as_seq(), Query::eq(0, int64(2)), one.clone());
+    let both = Table::new(
+        schema_kv(types),
+        vec![vec![int64(2), str("c")], vec![int64(2), str("c")]],
+    );
+    check_query(one.as_seq(), Query::union(one.into()), both);
+
+    let r = row(&[int64(1), str("a")]);
+    check_query(r.as_seq(), Query::eq(0, int64(1)), r.clone());
+    check_query(
+        r.as_seq(),
+        Query::eq(0, int64(2)),
+        Table::new(r.schema.clone(), vec![]),
+    );
+
+    let t1 = table_1();
+    assert_eq!(t1.as_seq().materialize().unwrap(), t1.clone().into());
+    assert_eq!(
+        Rel::from(int64(1)).as_seq().materialize().unwrap(),
+        Rel::One(int64(1))
+    );
+    assert_eq!(
+        Range::new(0, 4, 2).as_seq().materialize().unwrap(),
+        Vector::new_scalars(&[Scalar::ISize(0), Scalar::ISize(2)]).into()
+    );
+
+    let path = std::env::temp_dir().join("tablam_materialize.txt");
+    std::fs::write(&path, "a\nb\r\nc").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let lines = IoFile::new(file).into_seq();
+    assert_eq!(lines.shape, Shape::Stream(1));
+    let expected = Vector::new(
+        schema_single("line", DataType::UTF8),
+        vec![str("a"), str("b"), str("c")],
+    );
+    assert_eq!(lines.materialize().unwrap(), expected.into());
+    std::fs::remove_file(&path).unwrap();
+
+    //A error in the middle is not the end of the file
+    let path = std::env::temp_dir().join("tablam_materialize_bad.txt");
+    std::fs::write(&path, b"a\n\xff\nc").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let lines = IoFile::new(file).into_seq();
+    assert!(matches!(lines.materialize(), Err(Error::Io(_))));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_seq_reuse() {
+    let t1 = table_1();
+    let seq = t1.as_seq();
+    assert!(!seq.single_pass);
+    let copy = seq.clone();
+    assert_eq!(seq.materialize().unwrap(), t1.clone().into());
+    assert_eq!(copy.materialize().unwrap(), t1.clone().into());
+
+    let mut data = t1.data.clone();
+    data.extend(t1.data.clone());
+    let twice = Table::new(t1.schema.clone(), data);
+    check_query(seq.clone(), Query::union(seq.into()), twice);
+
+    let v = rel_nums1();
+    let once = Seq::once(v.schema.clone(), &v.shape(), v.rows());
+    let rel: Rel = once.clone().into();
+    let filtered = match rel.query(&[Query::eq(0, int64(2))]).unwrap() {
+        Rel::Seq(x) => x,
+        _ => unreachable!(),
+    };
+    assert!(filtered.single_pass);
+    assert_eq!(filtered.materialize().unwrap(), array(&[2i64]).into());
+    assert_eq!(filtered.materialize(), Err(Error::StreamConsumed));
+    assert_eq!(once.materialize(), Err(Error::StreamConsumed));
+}
+
+#[test]
+fn test_std_iter() {
+    let t1 = table_1();
+    let rows = t1.as_seq().iter_rows().unwrap();
+    assert_eq!(rows.size_hint(), (3, Some(3)));
+    assert_eq!(rows.collect::<Vec<_>>(), t1.data);
+
+    let rel: Rel = t1.as_seq().into();
+    let seq = match rel.query(&[Query::eq(0, int64(2))]).unwrap() {
+        Rel::Seq(x) => x,
+        _ => unreachable!(),
+    };
+    let mut rows = seq.iter_rows().unwrap();
+    assert_eq!(rows.size_hint(), (0, Some(3)));
+    assert_eq!(rows.next(), Some(t1.data[0].clone()));
+    assert_eq!(rows.size_hint(), (0, Some(2)));
+
+    let names: Vec<_> = rel_nums1()
+        .rows()
+        .into_rows()
+        .zip(t1.as_seq().iter_rows().unwrap())
+        .map(|(n, row)| vec![n[0].clone(), row[1].clone()])
+        .collect();
+    assert_eq!(names[2], vec![int64(3), t1.data[2][1].clone()]);
+
+    let schema = schema_it(DataType::I64);
+    let nums = Seq::of_iter(&schema, (1..4).map(|x| vec![int64(x)]));
+    assert_eq!(nums.shape, Shape::Vector(3));
+    assert!(nums.single_pass);
+    assert_eq!(nums.materialize().unwrap(), rel_nums1().into());
+
+    let evens = (1..)
+        .map(|x| vec![int64(x * 2)])
+        .take_while(|x| x[0] < int64(8));
+    let evens = Seq::of_iter(&schema, evens);
+    assert_eq!(evens.shape, Shape::Stream(1));
+    check_query(evens, Query::limit(1, 5), array(&[4i64, 6i64]));
+
+    //Re-iterable, making the std iterator again on each iteration
+    let data = t1.data.clone();
+    let again = Seq::new(t1.schema.clone(), &t1.shape(), move || {
+        Ok(Box::new(IterRel::new(2, data.clone().into_iter())))
+    });
+    assert_eq!(again.materialize().unwrap(), t1.clone().into());
+    assert_eq!(again.materialize().unwrap(), t1.into());
+
+    //A row of other width is a error, not a panic in the filter
+    let short = vec![vec![int64(1)], vec![]];
+    let short: Rel = Seq::of_iter(&schema, short.into_iter()).into();
+    let rel = short.query(&[Query::eq(0, int64(1))]).unwrap();
+    assert_eq!(to_rows(&rel), Err(Error::ArityMismatch(1, 1, 0)));
+}
+
+#[test]
+fn test_range() {
+    let zero = Range::try_new(0, 10, 0);
+    assert!(matches!(zero, Err(Error::Domain(_))));
+    let r1 = Range::try_new(0, 10, 3).unwrap();
+    assert_eq!(r1.count(), 4);
+
+    assert_eq!(r1.get(9), Some(9));
+    assert_eq!(r1.get(0), Some(0));
+    assert_eq!(r1.get(4), None);
+    assert_eq!(r1.get(10), None);
+    assert_eq!(Range::new(2, 5, 1).get(1), None);
+}
+
+#[test]
+fn test_where_range() {
+    let r1 = Range::new(0, 10, 2);
+    let value = |x: isize| Scalar::ISize(x);
+
+    check_query(r1.clone(), Query::less(0, value(5)), Range::new(0, 5, 2));
+    check_query(r1.clone(), Query::less_eq(0, value(4)), Range::new(0, 5, 2));
+    check_query(
+        r1.clone(),
+        Query::greater(0, value(3)),
+        Range::new(4, 10, 2),
+    );
+    check_query(
+        r1.clone(),
+        Query::greater_eq(0, int64(4)),
+        Range::new(4, 10, 2),
+    );
+    check_query(
+        r1.clone(),
+        Query::greater(0, value(20)),
+        Range::new(10, 10, 2),
+    );
+    check_query(r1.clone(), Query::eq(0, value(4)), Range::new(4, 5, 2));
+    check_query(r1.clone(), Query::eq(0, value(5)), Range::new(0, 0, 2));
+    check_query(r1.clone(), Query::less(0, value(-1)), Range::new(0, 0, 2));
+
+    let result = array(&[0isize, 2, 6, 8]);
+    check_query(r1.clone(), Query::not(0, value(4)), result);
+
+    //A non-integer bound compare as a number
+    check_query(r1.clone(), Query::less(0, float(2.5)), Range::new(0, 3, 2));
+    check_query(
+        r1.clone(),
+        Query::greater(0, dec(25, 1)),
+        Range::new(4, 10, 2),
+    );
+    check_query(
+        r1.clone(),
+        Query::less_eq(0, float(4.0)),
+        Range::new(0, 5, 2),
+    );
+    check_query(
+        r1.clone(),
+        Query::eq(0, float(2.5)),
+        array_empty(DataType::ISize),
+    );
+
+    //A predicate & his negation split the range, whatever the numeric type
+    let r2 = Range::new(0, 10, 1);
+    let preds = vec![
+        CmOp::less(0, float(2.5).into()).into(),
+        CmOp::greater_eq(0, dec(75, 1).into()).into(),
+        CmOp::eq(0, float(2.5).into()).into(),
+        Predicate::from(CmOp::eq(0, int64(4).into())),
+        CmOp::eq(0, int(4).into()).into(),
+        CmOp::less(0, int64(3).into()).into(),
+        CmOp::greater_eq(0, int(7).into()).into(),
+        Predicate::or(vec![
+            CmOp::eq(0, int64(1).into()).into(),
+            CmOp::eq(0, value(8).into()).into(),
+        ]),
+    ];
+    for pred in preds {
+        let yes = to_rows(&r2.filter(pred.clone()).unwrap()).unwrap();
+        let no = to_rows(&r2.filter(Predicate::negate(pred)).unwrap()).unwrap();
+        assert_eq!(yes.len() + no.len(), 10);
+        assert!(yes.iter().all(|x| !no.contains(x)));
+    }
+}
+
+#[test]
+fn test_sort() {
+    let asc = Query::sort_asc(colp(0));
+    let desc = Query::sort_desc(colp(0));
+
+    let s1 = int64(1);
+    check_query(s1.clone(), asc.clone(), s1);
+
+    let v1 = array(&[3i64, 1i64, 2i64]);
+    check_query(v1.clone(), asc.clone(), rel_nums1());
+    check_query(v1.clone(), desc.clone(), array(&[3i64, 2i64, 1i64]));
+    check_query(v1.as_seq(), desc.clone(), array(&[3i64, 2i64, 1i64]));
+
+    let r1 = Range::new(0, 3, 1);
+    check_query(r1.clone(), asc, r1.clone());
+    check_query(r1.clone(), desc, array(&[2isize, 1isize, 0isize]));
+    //Keep the name of the column
+    let renamed = r1.rename(&[(colp(0), "n".into())]).unwrap();
+    let result = Vector::new(
+        renamed.schema(),
+        vec![Scalar::ISize(2), Scalar::ISize(1), Scalar::ISize(0)],
+    );
+    check_query(renamed, Query::sort_desc(colp(0)), result);
+
+    let t1 = table_1();
+    let by_name = Query::sort(&[SortKey::asc(coln("name"))]);
+    let result = Table::new(
+        t1.schema.clone(),
+        vec![t1.data[2].clone(), t1.data[0].clone(), t1.data[1].clone()],
+    );
+    check_query(t1.clone(), by_name, result);
+
+    let by_name = Query::sort(&[SortKey::asc(coln("name")).nulls(Nulls::First)]);
+    let result = Table::new(
+        t1.schema.clone(),
+        vec![t1.data[1].clone(), t1.data[2].clone(), t1.data[0].clone()],
+    );
+    check_query(t1, by_name, result);
+}
+
+/// Count how many rows are pulled from it
+struct Counter {
+    pos: usize,
+    pulled: Rc<Cell<usize>>,
+}
+
+impl RelIter for Counter {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn advance(&mut self) -> bool {
+        self.pos += 1;
+        self.pulled.set(self.pos);
+        true
+    }
+
+    fn row(&mut self) -> Col {
+        vec![value(self.pos as i64)]
+    }
+}
+
+#[test]
+fn test_limit() {
+    let v1 = rel_nums1();
+    check_query(v1.clone(), Query::limit(1, 1), array(&[2i64]));
+    check_query(v1.clone(), Query::limit(2, 10), array(&[3i64]));
+    check_query(v1.clone(), Query::limit(5, 1), array_empty(DataType::I64));
+    check_query(v1.as_seq(), Query::limit(1, 2), array(&[2i64, 3i64]));
+
+    let s1 = int64(1);
+    check_query(s1.clone(), Query::limit(0, 1), s1.clone());
+    check_query(s1, Query::limit(1, 1), array_empty(DataType::I64));
+
+    let t1 = table_1();
+    let result = Table::new(t1.schema.clone(), vec![t1.data[1].clone()]);
+    check_query(t1, Query::limit(1, 1), result);
+
+    let r1 = Range::new(0, 10, 2);
+    check_schema(&r1, 1, 5);
+    check_query(r1.clone(), Query::limit(1, 2), Range::new(2, 6, 2));
+    check_query(r1.clone(), Query::limit(4, 9), Range::new(8, 10, 2));
+    let rel: Rel = r1.as_seq().into();
+    let rows = to_rows(&rel.query(&[Query::limit(1, 2)]).unwrap()).unwrap();
+    assert_eq!(rows, vec![vec![value(2isize)], vec![value(4isize)]]);
+
+    let pulled = Rc::new(Cell::new(0));
+    let counter = Counter {
+        pos: 0,
+        pulled: pulled.clone(),
+    };
+    let seq = Seq::once(
+        schema_it(DataType::I64),
+        &Shape::Vector(usize::MAX),
+        counter,
+    );
+    check_query(seq, Query::limit(2, 3), array(&[3i64, 4i64, 5i64]));
+    assert_eq!(pulled.get(), 5);
+}
+
+#[test]
+fn test_distinct() {
+    let v1 = array(&[1i64, 2i64, 1i64, 3i64, 2i64]);
+    check_query(v1.clone(), Query::distinct(), rel_nums1());
+    check_query(v1.as_seq(), Query::distinct(), rel_nums1());
+
+    let t2 = table_2();
+    let first = Table::new(
+        t2.schema.clone(),
+        vec![t2.data[0].clone(), t2.data[2].clone()],
+    );
+    let on = Query::distinct_on(&[coln("customer")]);
+    check_query(t2.clone(), on, first);
+    check_query(t2.clone(), Query::distinct(), t2.clone());
+
+    let rel: Rel = t2.clone().into();
+    let query = [Query::union(t2.clone().into()), Query::distinct()];
+    assert_eq!(rel.query(&query).unwrap(), t2.into());
+}
+
+#[test]
+fn test_extend() {
+    let items = Table::new(
+        schema(&[("qty", DataType::I64), ("price", DataType::I64)]),
+        vec![vec![int64(2), int64(10)], vec![int64(3), int64(5)]],
+    );
+    let total = RowExpr::bin_op(BinOp::Mul, RowExpr::col(coln("qty")), RowExpr::col(colp(1)));
+    let result = Table::new(
+        schema(&[
+            ("qty", DataType::I64),
+            ("price", DataType::I64),
+            ("total", DataType::I64),
+        ]),
+        vec![
+            vec![int64(2), int64(10), int64(20)],
+            vec![int64(3), int64(5), int64(15)],
+        ],
+    );
+    let query = Query::extend("total", total);
+    check_query(items.clone(), query.clone(), result.clone());
+
+    let rel: Rel = items.as_seq().into();
+    let rel = rel.query(&[query]).unwrap();
+    assert!(matches!(rel, Rel::Seq(_)));
+    assert_eq!(rel.schema(), result.schema);
+    assert_eq!(to_rows(&rel).unwrap(), result.data);
+
+    //Lazy: only the rows asked are pulled from the source
+    let pulled = Rc::new(Cell::new(0));
+    let counter = Counter {
+        pos: 0,
+        pulled: pulled.clone(),
+    };
+    let seq = Seq::once(
+        schema_it(DataType::I64),
+        &Shape::Vector(usize::MAX),
+        counter,
+    );
+    let double = RowExpr::bin_op(BinOp::Mul, RowExpr::col(colp(0)), RowExpr::value(int64(2)));
+    let rel: Rel = seq.into();
+    let rel = rel
+        .query(&[Query::extend("double", double), Query::limit(0, 2)])
+        .unwrap();
+    let rows = to_rows(&rel).unwrap();
+    assert_eq!(rows[1], vec![int64(2), int64(4)]);
+    assert_eq!(pulled.get(), 2);
+
+    //The error of a row stop the stream
+    let big = RowExpr::bin_op(
+        BinOp::Mul,
+        RowExpr::col(colp(0)),
+        RowExpr::value(int64(i64::MAX)),
+    );
+    let rel: Rel = items.as_seq().into();
+    let rel = rel.query(&[Query::extend("big", big.clone())]).unwrap();
+    assert_eq!(to_rows(&rel), Err(Error::Overflow(DataType::I64)));
+
+    //Unless the query ask to saturate or return null
+    let big_col = |rel: Rel| -> Vec<Scalar> {
+        to_rows(&rel)
+            .unwrap()
+            .into_iter()
+            .map(|x| x[2].clone())
+            .collect()
+    };
+    let query = Query::extend_with("big", big.clone(), OnError::Saturate);
+    let rel: Rel = items.as_seq().into();
+    let saturated = big_col(rel.query(&[query]).unwrap());
+    assert_eq!(saturated, vec![int64(i64::MAX), int64(i64::MAX)]);
+    let query = Query::extend_with("big", big, OnError::Null);
+    let rel: Rel = items.into();
+    assert_eq!(big_col(rel.query(&[query]).unwrap()), vec![none(), none()]);
+}
+
+#[test]
+fn test_rename() {
+    let t1 = table_1();
+    let renamed = schema(&[("key", DataType::I64), ("name", DataType::UTF8)]);
+    let result = Table::new(renamed, t1.data.clone());
+    check_query(t1.clone(), Query::rename(&[(coln("id"), "key")]), result);
+
+    let v1 = rel_nums1();
+    let result = Vector::new(schema_single("n", DataType::I64), v1.data.clone());
+    check_query(v1, Query::rename(&[(colp(0), "n")]), result);
+
+    //A name can't be repeated, but can be swapped
+    let rel: Rel = t1.clone().into();
+    let clash = rel.clone().query(&[Query::rename(&[(coln("id"), "name")])]);
+    assert_eq!(clash.err(), Some(Error::DuplicateField("name".into())));
+    let swap = Query::rename(&[(coln("id"), "name"), (coln("name"), "id")]);
+    let swapped = rel.clone().query(&[swap]).unwrap();
+    assert_eq!(swapped.schema().columns[0].name, "name");
+
+    let label = RowExpr::value(str("x"));
+    let clash = rel.query(&[Query::extend("id", label)]);
+    assert_eq!(clash.err(), Some(Error::DuplicateField("id".into())));
+}
+
+#[test]
+fn test_group() {
+    let t2 = table_2();
+    let id = || coln("id");
+    let aggs = [
+        Agg::count(id()),
+        Agg::sum(id()),
+        Agg::min(id()),
+        Agg::max(id()),
+        Agg::avg(id()).named("mean"),
+        Agg::first(id()),
+        Agg::last(id()),
+        Agg::count_distinct(id()),
+    ];
+    let by_customer = schema(&[
+        ("customer", DataType::I64),
+        ("count_id", DataType::I64),
+        ("sum_id", DataType::I64),
+        ("min_id", DataType::I64),
+        ("max_id", DataType::I64),
+        ("mean", DataType::Decimal),
+        ("first_id", DataType::I64),
+        ("last_id", DataType::I64),
+        ("countdistinct_id", DataType::I64),
+    ]);
+    let data = vec![
+        vec![
+            int64(1),
+            int64(2),
+            int64(21),
+            int64(10),
+            int64(11),
+            Decimal::new(105, 1).into(),
+            int64(10),
+            int64(11),
+            int64(2),
+        ],
+        vec![
+            int64(4),
+            int64(1),
+            int64(12),
+            int64(12),
+            int64(12),
+            Decimal::new(12, 0).into(),
+            int64(12),
+            int64(12),
+            int64(1),
+        ],
+    ];
+    let result = Table::new(by_customer, data);
+    let query = Query::group(&[coln("customer")], &aggs);
+    check_query(t2.clone(), query.clone(), result.clone());
+    check_query(t2.as_seq(), query, result);
+
+    let t1 = table_1();
+    let name = || coln("name");
+    let aggs = [
+        Agg::count(name()),
+        Agg::min(name()),
+        Agg::last(name()),
+        Agg::sum(coln("id")),
+    ];
+    let schema = schema(&[
+        ("count_name", DataType::I64),
+        ("min_name", DataType::UTF8),
+        ("last_name", DataType::UTF8),
+        ("sum_id", DataType::I64),
+    ]);
+    let result = Table::new(
+        schema.clone(),
+        vec![vec![int64(2), str("a"), str("a"), int64(6)]],
+    );
+    check_query(t1.clone(), Query::group(&[], &aggs), result);
+
+    //Over no rows: count 0, the others null
+    let empty = Table::new(t1.schema.clone(), vec![]);
+    let result = Table::new(schema, vec![vec![int64(0), none(), none(), none()]]);
+    check_query(empty.clone(), Query::group(&[], &aggs), result);
+    let rel: Rel = empty.into();
+    let by_name = rel.query(&[Query::group(&[name()], &aggs)]).unwrap();
+    assert!(to_rows(&by_name).unwrap().is_empty());
+
+    //The avg of floats is a float, even if too large for a Decimal
+    let floats = Vector::new(schema_it(DataType::F64), vec![float(1e30), float(3e30)]);
+    let rel: Rel = floats.into();
+    let result = rel
+        .query(&[Query::group(&[], &[Agg::avg(colp(0))])])
+        .unwrap();
+    assert_eq!(result.schema()[0].kind, DataType::F64);
+    assert_eq!(to_rows(&result).unwrap(), vec![vec![float(2e30)]]);
+
+    //A overflow fail the sum, unless the aggregate ask to saturate or for null
+    let big = Vector::new(schema_it(DataType::I64), vec![int64(i64::MAX), int64(1)]);
+    let rel: Rel = big.into();
+    let result = rel
+        .clone()
+        .query(&[Query::group(&[], &[Agg::sum(colp(0))])]);
+    assert_eq!(result.err(), Some(Error::Overflow(DataType::I64)));
+    let aggs = [
+        Agg::sum(colp(0)).on_error(OnError::Null),
+        Agg::avg(colp(0)).on_error(OnError::Null),
+    ];
+    let result = rel.clone().query(&[Query::group(&[], &aggs)]).unwrap();
+    assert_eq!(to_rows(&result).unwrap(), vec![vec![none(), none()]]);
+    let aggs = [Agg::sum(colp(0)).on_error(OnError::Saturate)];
+    let result = rel.clone().query(&[Query::group(&[], &aggs)]).unwrap();
+    assert_eq!(to_rows(&result).unwrap(), vec![vec![int64(i64::MAX)]]);
+}
+
+#[test]
+fn test_nest() {
+    let t2 = table_2();
+    let items = schema(&[("id", DataType::I64)]);
+    let nested = |ids: &[i64]| {
+        let rows = ids.iter().map(|x| vec![int64(*x)]).collect();
+        Scalar::Rel(Rc::new(Table::new(items.clone(), rows).into()))
+    };
+    let by_customer = schema(&[
+        ("customer", DataType::I64),
+        ("items", DataType::Rel(items.clone())),
+    ]);
+    let data = vec![
+        vec![int64(1), nested(&[10, 11])],
+        vec![int64(4), nested(&[12])],
+    ];
+    let result = Table::new(by_customer, data);
+    let query = Query::nest(&[coln("customer")], "items");
+    check_query(t2.clone(), query.clone(), result.clone());
+    check_query(t2.as_seq(), query, result.clone());
+
+    assert_eq!(format!("{}", result.data[0][1]), "[id:I64; 10; 11]");
+    assert_eq!(
+        format!("{}", result.schema),
+        "customer:I64, items:Rel(id:I64)"
+    );
+
+    let flat = schema(&[("customer", DataType::I64), ("id", DataType::I64)]);
+    let data = vec![
+        vec![int64(1), int64(10)],
+        vec![int64(1), int64(11)],
+        vec![int64(4), int64(12)],
+    ];
+    check_query(result, Query::unnest(coln("items")), Table::new(flat, data));
+
+    let err = t2.unnest(&coln("id"));
+    assert_eq!(err, Err(Error::Unsupported("unnest".into(), DataType::I64)));
 }
 
 #[test]
@@ -47,10 +984,268 @@ fn test_union() {
 }
 
 #[test]
-fn test_intersection() {}
+fn test_union_mixed() {
+    let r1 = Range::new(0, 3, 1);
+    check_query(
+        r1.clone(),
+        Query::union(Range::new(3, 5, 1).into()),
+        Range::new(0, 5, 1),
+    );
+    //Like any other union, the overlap is repeated
+    let overlap = array(&[0isize, 1, 2, 2, 3, 4]);
+    check_query(
+        r1.clone(),
+        Query::union(Range::new(2, 5, 1).into()),
+        overlap,
+    );
+    //A range with other schema is a mismatch, like for any relation
+    let renamed = Range::new(3, 5, 1)
+        .rename(&[(colp(0), "n".into())])
+        .unwrap();
+    for query in [Query::union(renamed.clone()), Query::intersection(renamed)] {
+        let result = Rel::from(r1.clone()).query(&[query]);
+        assert!(matches!(result, Err(Error::SchemaMismatch(_, _))));
+    }
+
+    let rel: Rel = r1.clone().into();
+    let query = [Query::union(Range::new(2, 5, 1).into()), Query::distinct()];
+    assert_eq!(
+        rel.query(&query).unwrap(),
+        array(&[0isize, 1, 2, 3, 4]).into()
+    );
+    let result = array(&[0isize, 1, 2, 10]);
+    check_query(
+        r1.clone(),
+        Query::union(value(10isize).into()),
+        result.clone(),
+    );
+    let rel: Rel = r1.as_seq().into();
+    let rows = to_rows(&rel.query(&[Query::union(value(10isize).into())]).unwrap()).unwrap();
+    assert_eq!(rows, to_columns(result.data));
+
+    let v1 = rel_nums1();
+    let t1 = Table::new(
+        v1.schema.clone(),
+        to_columns(nums_2().iter().map(|x| value(*x)).collect()),
+    );
+    let result = array(&[1i64, 2, 3, 4, 5, 6]);
+    check_query(v1, Query::union(t1.into()), result);
+
+    let t1 = table_1();
+    let swapped = Table::new(
+        schema(&[("name", DataType::UTF8), ("id", DataType::I64)]),
+        vec![vec![str("c"), int64(4)]],
+    );
+    let mut data = t1.data.clone();
+    data.push(vec![int64(4), str("c")]);
+    let result = Table::new(t1.schema.clone(), data);
+    check_query(t1, Query::union(swapped.into()), result);
+}
+
+#[test]
+fn test_intersection() {
+    let v1 = rel_nums1();
+    let v3 = rel_nums3();
+    let result = array(&[2i64, 3i64]);
+    check_query(
+        v1.clone(),
+        Query::intersection(v3.clone().into()),
+        result.clone(),
+    );
+    check_query(v1.as_seq(), Query::intersection(v3.clone().into()), result);
+
+    check_query(int64(2), Query::intersection(v3.clone().into()), int64(2));
+    check_query(
+        int64(1),
+        Query::intersection(v3.into()),
+        array_empty(DataType::I64),
+    );
+
+    let r1 = Range::new(0, 10, 2);
+    check_query(
+        r1.clone(),
+        Query::intersection(Range::new(4, 7, 2).into()),
+        Range::new(4, 7, 2),
+    );
+    let odd = array(&[1isize, 2, 3, 4]);
+    check_query(r1, Query::intersection(odd.into()), array(&[2isize, 4]));
+}
+
+#[test]
+fn test_difference() {
+    let v1 = rel_nums1();
+    let v3 = rel_nums3();
+    let result = array(&[1i64]);
+    check_query(v1.clone(), Query::diff(v3.clone().into()), result.clone());
+    check_query(v1.as_seq(), Query::diff(v3.as_seq().into()), result);
+
+    check_query(int64(1), Query::diff(v3.clone().into()), int64(1));
+    check_query(int64(2), Query::diff(v3.into()), array_empty(DataType::I64));
+
+    let r1 = Range::new(0, 5, 1);
+    let result = array(&[0isize, 2, 4]);
+    check_query(r1, Query::diff(array(&[1isize, 3]).into()), result);
+
+    let t1 = table_1();
+    let t2 = Table::new(t1.schema.clone(), vec![t1.data[0].clone()]);
+    let result = Table::new(t1.schema.clone(), t1.data[1..].to_vec());
+    check_query(t1, Query::diff(t2.into()), result);
+}
+
+#[test]
+fn test_set_incompatible() {
+    let rel: Rel = rel_nums1().into();
+    let result = rel.query(&[Query::union(table_1().into())]);
+    assert!(matches!(result, Err(Error::SchemaMismatch(_, _))));
+}
+
+#[test]
+fn test_errors() {
+    let rel: Rel = table_1().into();
+
+    let result = rel.clone().query(&[Query::select(&[coln("missing")])]);
+    assert_eq!(result, Err(Error::FieldNotFound("missing".into())));
+
+    let result = rel.clone().query(&[Query::eq(5, int64(1))]);
+    assert_eq!(result, Err(Error::ColumnOutOfRange(5, 2)));
+
+    let total = RowExpr::bin_op(
+        BinOp::Add,
+        RowExpr::col(coln("id")),
+        RowExpr::col(coln("name")),
+    );
+    let result = rel.query(&[Query::extend("total", total)]);
+    assert_eq!(
+        result,
+        Err(Error::TypeMismatch(DataType::I64, DataType::UTF8))
+    );
+
+    let result = i64::try_from(str("a"));
+    assert_eq!(
+        result,
+        Err(Error::InvalidCast(DataType::UTF8, DataType::I64))
+    );
+}
 
 #[test]
-fn test_difference() {}
+fn test_joins() {
+    let t1 = table_1();
+    let t2 = table_2();
+    let on = [JoinCmp::eq(coln("id"), coln("customer"))];
+    let schema = schema(&[
+        ("id", DataType::I64),
+        ("name", DataType::UTF8),
+        ("id_0", DataType::I64),
+        ("customer", DataType::I64),
+    ]);
+
+    let row = |a: Scalar, b: Scalar, c: Scalar, d: Scalar| vec![a, b, c, d];
+    let matched = vec![
+        row(int64(1), none(), int64(10), int64(1)),
+        row(int64(1), none(), int64(11), int64(1)),
+    ];
+    let no_left = row(none(), none(), int64(12), int64(4));
+    let no_right = |pos: usize| {
+        let mut x = t1.data[pos].clone();
+        x.extend(vec![none(), none()]);
+        x
+    };
+
+    let inner = Table::new(schema.clone(), matched.clone());
+    check_query(t1.clone(), Query::inner(&on, t2.clone().into()), inner);
+
+    let mut data = vec![no_right(0)];
+    data.extend(matched.clone());
+    data.push(no_right(2));
+    let left = Table::new(schema.clone(), data.clone());
+    check_query(t1.clone(), Query::left(&on, t2.clone().into()), left);
+
+    let mut right = matched.clone();
+    right.push(no_left.clone());
+    let right = Table::new(schema.clone(), right);
+    check_query(t1.clone(), Query::right(&on, t2.clone().into()), right);
+
+    data.push(no_left.clone());
+    let full = Table::new(schema.clone(), data);
+    check_query(t1.clone(), Query::full(&on, t2.clone().into()), full);
+
+    let less = [JoinCmp::less(coln("id"), coln("customer"))];
+    let data = (0..3)
+        .map(|x| {
+            let mut x = t1.data[x].clone();
+            x.extend(t2.data[2].clone());
+            x
+        })
+        .collect();
+    let less_join = Table::new(schema, data);
+    check_query(t1.as_seq(), Query::inner(&less, t2.into()), less_join);
+
+    //The keys of numbers of other types are compared as the larger
+    let prices = Table::new(
+        schema_build(&[("key", DataType::Decimal), ("price", DataType::I64)]),
+        vec![vec![dec(1, 0), int64(5)], vec![dec(15, 1), int64(7)]],
+    );
+    let on = [JoinCmp::eq(coln("id"), coln("key"))];
+    let joined = t1.join(&prices.clone().into(), Join::Inner, &on).unwrap();
+    let rows = to_rows(&joined).unwrap();
+    assert_eq!(rows, vec![vec![int64(1), none(), dec(1, 0), int64(5)]]);
+
+    let on = [JoinCmp::eq(coln("name"), coln("key"))];
+    let result = t1.join(&prices.into(), Join::Inner, &on);
+    assert_eq!(
+        result.err(),
+        Some(Error::TypeMismatch(DataType::UTF8, DataType::Decimal))
+    );
+}
 
 #[test]
-fn test_joins() {}
+fn test_joins_natural_cross() {
+    let t1 = table_1();
+    let totals = Table::new(
+        schema(&[("id", DataType::I64), ("total", DataType::I64)]),
+        vec![vec![int64(1), int64(100)], vec![int64(3), int64(300)]],
+    );
+
+    let schema_all = schema(&[
+        ("id", DataType::I64),
+        ("name", DataType::UTF8),
+        ("total", DataType::I64),
+    ]);
+    let result = Table::new(
+        schema_all,
+        vec![
+            vec![int64(1), none(), int64(100)],
+            vec![int64(3), str("a"), int64(300)],
+        ],
+    );
+    check_query(t1.clone(), Query::natural(totals.into()), result);
+
+    let flags = array(&[true, false]);
+    let rel: Rel = t1.into();
+    if let Rel::Table(cross) = rel.query(&[Query::cross(flags.into())]).unwrap() {
+        check_schema(&cross, 3, 6);
+    } else {
+        panic!("Cross join must return a table");
+    }
+}
+
+#[test]
+fn test_joins_semi_anti() {
+    let t1 = table_1();
+    let t2 = table_2();
+    let on = [JoinCmp::eq(coln("id"), coln("customer"))];
+
+    let with_orders = Table::new(t1.schema.clone(), vec![t1.data[1].clone()]);
+    check_query(t1.clone(), Query::semi(&on, t2.clone().into()), with_orders);
+
+    let without = Table::new(
+        t1.schema.clone(),
+        vec![t1.data[0].clone(), t1.data[2].clone()],
+    );
+    check_query(t1.clone(), Query::anti(&on, t2.clone().into()), without);
+
+    let rel: Rel = t1.clone().into();
+    let query = [Query::anti(&on, t2.into()), Query::eq(0, int64(3))];
+    let result = Table::new(t1.schema.clone(), vec![t1.data[2].clone()]);
+    assert_eq!(rel.query(&query).unwrap(), result.into());
+}
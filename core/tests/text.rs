@@ -0,0 +1,71 @@
+use tablam_core::dsl::*;
+use tablam_core::error::*;
+use tablam_core::stdlib::text::*;
+use tablam_core::types::*;
+
+#[test]
+fn test_len_case() {
+    let word = str("Straße");
+    assert_eq!(text_len(&word), Ok(int64(6)));
+    assert_eq!(text_len_bytes(&word), Ok(int64(7)));
+    assert_eq!(text_upper(&word), Ok(str("STRASSE")));
+    assert_eq!(text_lower(&word), Ok(str("straße")));
+    assert_eq!(text_fold(&word), text_fold(&str("STRASSE")));
+    assert_eq!(text_fold(&str("ΣΊΣΥΦΟΣ")), text_fold(&str("σίσυφος")));
+    assert_eq!(text_len(&none()), Ok(none()));
+    assert_eq!(
+        text_upper(&int(1)),
+        Err(Error::InvalidCast(DataType::I32, DataType::UTF8))
+    );
+}
+
+#[test]
+fn test_trim_pad() {
+    let word = str("  año ");
+    assert_eq!(text_trim(&word), Ok(str("año")));
+    assert_eq!(text_trim_start(&word), Ok(str("año ")));
+    assert_eq!(text_trim_end(&word), Ok(str("  año")));
+
+    let num = str("42");
+    assert_eq!(text_pad_left(&num, &int(5), &str("0")), Ok(str("00042")));
+    assert_eq!(text_pad_right(&num, &int(6), &str("ab")), Ok(str("42abab")));
+    assert_eq!(text_pad_left(&num, &int(1), &str("0")), Ok(num.clone()));
+    assert!(matches!(
+        text_pad_left(&num, &int(5), &str("")),
+        Err(Error::Domain(_))
+    ));
+}
+
+#[test]
+fn test_search() {
+    let word = str("añoaño");
+    assert_eq!(text_substring(&word, &int(1), &int(2)), Ok(str("ño")));
+    assert_eq!(text_substring(&word, &int(5), &int(9)), Ok(str("o")));
+    assert_eq!(text_substring(&word, &int(9), &int(1)), Ok(str("")));
+    assert!(matches!(
+        text_substring(&word, &int(-1), &int(1)),
+        Err(Error::Domain(_))
+    ));
+    assert_eq!(text_find(&word, &str("o")), Ok(int64(2)));
+    assert_eq!(text_find(&word, &str("x")), Ok(none()));
+    assert_eq!(text_replace(&word, &str("ñ"), &str("n")), Ok(str("anoano")));
+    assert_eq!(text_starts_with(&word, &str("añ")), Ok(bool(true)));
+    assert_eq!(text_ends_with(&word, &str("añ")), Ok(bool(false)));
+}
+
+#[test]
+fn test_split_join() {
+    let parts = text_split(&str("a,b,,c"), &str(",")).unwrap();
+    assert_eq!(parts, array(&["a", "b", "", "c"]));
+    let parts = text_split(&none(), &str(",")).unwrap();
+    assert_eq!(parts.data, vec![none()]);
+    let parts = text_split(&str("a,b"), &none()).unwrap();
+    assert_eq!(parts.data, vec![none()]);
+    assert_eq!(
+        text_join(&[str("a"), none(), str("c")], &str("-")),
+        Ok(str("a-c"))
+    );
+    assert_eq!(text_join(&[str("a")], &none()), Ok(none()));
+    assert_eq!(text_concat(&str("a"), &str("b")), Ok(str("ab")));
+    assert_eq!(text_concat(&str("a"), &none()), Ok(none()));
+}
@@ -0,0 +1,147 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use tablam_core::dsl::*;
+use tablam_core::error::*;
+use tablam_core::stdlib::math::*;
+use tablam_core::stdlib::time::*;
+use tablam_core::types::*;
+
+fn date(y: i32, m: u32, d: u32) -> Scalar {
+    NaiveDate::from_ymd(y, m, d).into()
+}
+
+fn time(h: u32, m: u32, s: u32) -> Scalar {
+    NaiveTime::from_hms(h, m, s).into()
+}
+
+fn date_time(offset: i32, y: i32, m: u32, d: u32, h: u32, mi: u32) -> TimeStamp {
+    FixedOffset::east(offset * 3600)
+        .ymd(y, m, d)
+        .and_hms(h, mi, 0)
+}
+
+#[test]
+fn test_parse_format() {
+    let d = time_parse_date(&"2019-03-07".into(), &"%Y-%m-%d".into());
+    assert_eq!(d, Ok(date(2019, 3, 7)));
+    assert_eq!(
+        time_format(&date(2019, 3, 7), &"%d/%m/%Y".into()),
+        Ok("07/03/2019".into())
+    );
+    assert_eq!(
+        time_parse_time(&"13:05:01".into(), &"%H:%M:%S".into()),
+        Ok(time(13, 5, 1))
+    );
+
+    //Without offset is UTC
+    let utc = time_parse_datetime(&"2019-03-07 10:30".into(), &"%Y-%m-%d %H:%M".into());
+    assert_eq!(utc, Ok(date_time(0, 2019, 3, 7, 10, 30).into()));
+    let local = time_parse_datetime(
+        &"2019-03-07 10:30 -0500".into(),
+        &"%Y-%m-%d %H:%M %z".into(),
+    );
+    assert_eq!(local, Ok(date_time(-5, 2019, 3, 7, 10, 30).into()));
+
+    assert_eq!(
+        time_parse_date(&"07/03".into(), &"%Y-%m-%d".into()),
+        Err(Error::InvalidCast(DataType::UTF8, DataType::Date))
+    );
+    assert!(time_format(&date(2019, 3, 7), &"%H".into()).is_err());
+    assert_eq!(time_format(&Scalar::None, &"%Y".into()), Ok(Scalar::None));
+}
+
+#[test]
+fn test_part_trunc() {
+    let at: Scalar = date_time(-5, 2019, 3, 7, 22, 30).into();
+    assert_eq!(time_part(&at, TimePart::Year), Ok(int64(2019)));
+    assert_eq!(time_part(&at, TimePart::Day), Ok(int64(7)));
+    assert_eq!(time_part(&at, TimePart::Hour), Ok(int64(22)));
+    assert_eq!(
+        time_part(&date(2019, 3, 7), TimePart::Weekday),
+        Ok(int64(4))
+    );
+    assert_eq!(
+        time_part(&date(2019, 2, 1), TimePart::DayOfYear),
+        Ok(int64(32))
+    );
+    assert_eq!(
+        time_part(&date(2019, 3, 7), TimePart::Hour),
+        Err(Error::Unsupported("Hour".into(), DataType::Date))
+    );
+
+    assert_eq!(
+        time_trunc(&date(2019, 3, 7), TimePart::Month),
+        Ok(date(2019, 3, 1))
+    );
+    assert_eq!(
+        time_trunc(&date(2019, 3, 7), TimePart::Year),
+        Ok(date(2019, 1, 1))
+    );
+    assert_eq!(
+        time_trunc(&time(13, 5, 1), TimePart::Hour),
+        Ok(time(13, 0, 0))
+    );
+    assert_eq!(
+        time_trunc(&at, TimePart::Day),
+        Ok(date_time(-5, 2019, 3, 7, 0, 0).into())
+    );
+    assert_eq!(
+        time_trunc(&at, TimePart::Month),
+        Ok(date_time(-5, 2019, 3, 1, 0, 0).into())
+    );
+}
+
+#[test]
+fn test_arithmetic() {
+    let day = time_duration(&int(1), TimePart::Day).unwrap();
+    assert_eq!(day, Duration::days(1).into());
+    assert_eq!(math_add(&date(2019, 2, 28), &day), Ok(date(2019, 3, 1)));
+    assert_eq!(math_add(&day, &date(2019, 2, 28)), Ok(date(2019, 3, 1)));
+    assert_eq!(math_minus(&date(2019, 3, 1), &day), Ok(date(2019, 2, 28)));
+    assert_eq!(
+        math_minus(&date(2019, 3, 1), &date(2019, 2, 1)),
+        Ok(Duration::days(28).into())
+    );
+    assert_eq!(
+        math_add(&time(23, 0, 0), &Duration::hours(2).into()),
+        Ok(time(1, 0, 0))
+    );
+    assert_eq!(math_add(&day, &day), Ok(Duration::days(2).into()));
+    assert_eq!(math_add(&date(2019, 3, 1), &Scalar::None), Ok(Scalar::None));
+    assert_eq!(
+        math_mul(&Scalar::None, &day),
+        Err(Error::TypeMismatch(DataType::None, DataType::Duration))
+    );
+
+    assert_eq!(
+        math_add(&date(2019, 3, 1), &date(2019, 3, 1)),
+        Err(Error::TypeMismatch(DataType::Date, DataType::Date))
+    );
+    assert_eq!(
+        math_mul(&day, &int(2)),
+        Err(Error::TypeMismatch(DataType::Duration, DataType::I32))
+    );
+    assert_eq!(
+        math_add(&date(262_000, 1, 1), &Duration::days(1_000_000).into()),
+        Err(Error::Overflow(DataType::Date))
+    );
+    assert_eq!(
+        time_duration(&int64(i64::MAX), TimePart::Day),
+        Err(Error::Overflow(DataType::Duration))
+    );
+
+    let schema = schema_single("at", DataType::Date);
+    let start = RowExpr::value(date(2019, 1, 1));
+    let e = RowExpr::bin_op(BinOp::Minus, RowExpr::col(colp(0)), start);
+    assert_eq!(e.kind(&schema), Ok(DataType::Duration));
+}
+
+#[test]
+fn test_frozen() {
+    freeze(date_time(0, 2019, 3, 7, 23, 59));
+    assert_eq!(today(), NaiveDate::from_ymd(2019, 3, 7));
+    assert_eq!(now(), date_time(0, 2019, 3, 7, 23, 59));
+    unfreeze();
+    assert!(now().year() > 2019);
+}
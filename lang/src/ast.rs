@@ -4,6 +4,8 @@ use std::collections::HashMap;
 
 use tablam_core::types as TT;
 use tablam_core::dsl as DD;
+use tablam_core::error as EE;
+use tablam_core::stdlib::NativeFun;
 
 #[derive(Clone)]
 pub struct SourceMap {
@@ -75,6 +77,8 @@ impl Env {
 
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub on_error: TT::OnError,
+    pub natives: HashMap<String, NativeFun>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -116,6 +120,12 @@ pub struct BinOp {
     pub rhs: Value
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnaryOp {
+    pub op:    TT::UnaryOp,
+    pub value: Value,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Fail {
     pub msg: String,
@@ -126,6 +136,7 @@ pub enum Failed {
     Runtime(Fail),
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Var(String),
@@ -152,6 +163,7 @@ pub enum Expr {
 //    RelOp(TT::RelOp, BExpr),
 //    IndexOp(TT::IndexOp, BExpr),
     BinOp(BinOp),
+    UnaryOp(UnaryOp),
     CmpOp(CmOp),
     //Vars
     Var(String),
@@ -178,7 +190,7 @@ impl Expr {
 }
 
 impl <T> From<T> for Value
-    where T: From<TT::Scalar>, TT::Scalar: From<T>
+    where TT::Scalar: From<T>
 {
     fn from(of: T) -> Self {
         Value::Value(Rc::new(of.into()))
@@ -186,7 +198,7 @@ impl <T> From<T> for Value
 }
 
 impl <T> From<T> for Expr
-    where T: From<TT::Scalar>, TT::Scalar: From<T>
+    where TT::Scalar: From<T>
 {
     fn from(of: T) -> Self {
         Expr::Value(of.into())
@@ -200,6 +212,13 @@ impl From<Expr> for Value
     }
 }
 
+impl From<EE::Error> for Failed
+{
+    fn from(of: EE::Error) -> Self {
+        Failed::Runtime(Fail { msg: of.to_string() })
+    }
+}
+
 impl From<CmOp> for Expr
 {
     fn from(of: CmOp) -> Self {
@@ -310,6 +329,30 @@ pub fn mul_op(lhs:Value, rhs:Value) -> Expr {
     bin_op(TT::BinOp::Mul, lhs, rhs)
 }
 
+pub fn mod_op(lhs:Value, rhs:Value) -> Expr {
+    bin_op(TT::BinOp::Mod, lhs, rhs)
+}
+
+pub fn int_div_op(lhs:Value, rhs:Value) -> Expr {
+    bin_op(TT::BinOp::IntDiv, lhs, rhs)
+}
+
+pub fn pow_op(lhs:Value, rhs:Value) -> Expr {
+    bin_op(TT::BinOp::Pow, lhs, rhs)
+}
+
+pub fn unary_op(op:TT::UnaryOp, value:Value) -> Expr {
+    Expr::UnaryOp(UnaryOp{op, value})
+}
+
+pub fn neg_op(value:Value) -> Expr {
+    unary_op(TT::UnaryOp::Neg, value)
+}
+
+pub fn abs_op(value:Value) -> Expr {
+    unary_op(TT::UnaryOp::Abs, value)
+}
+
 pub fn fun_def(name:&str, pars:&[(&str, TT::DataType)], ret_ty:TT::DataType, body: BExpr) -> Expr {
     let params = DD::schema(pars);
 
@@ -1,17 +1,27 @@
+use std::collections::HashMap;
+
 use super::ast::*;
-use tablam_core::stdlib::math::*;
+use tablam_core::stdlib::text;
+use tablam_core::stdlib::time;
+use tablam_core::stdlib::NativeFun;
 use tablam_core::types as TT;
-use tablam_core::types::BinOp as BP;
 use tablam_core::types::CompareOp as CP;
 
 impl Program {
     pub fn new() -> Self {
-        Program {}
+        let mut program = Program {
+            on_error: TT::OnError::default(),
+            natives: HashMap::new(),
+        };
+        for fun in text::functions().into_iter().chain(time::functions()) {
+            program.register_function_native(fun);
+        }
+        program
     }
 
-    //    fn register_function_native(&self,` expr: &FunDef) {
-    //
-    //    }
+    fn register_function_native(&mut self, fun: NativeFun) {
+        self.natives.insert(fun.name.to_string(), fun);
+    }
 
     fn register_function(&self, env: &mut Env, expr: FunDef) {
         env.add_fun(expr);
@@ -76,13 +86,13 @@ impl Program {
         let lhs = self.decode_value(env, &expr.lhs)?;
         let rhs = self.decode_value(env, &expr.rhs)?;
 
-        let result = match expr.op {
-            BP::Add => math_add(&lhs, &rhs),
-            BP::Minus => math_minus(&lhs, &rhs),
-            BP::Mul => math_mul(&lhs, &rhs),
-            BP::Div => math_div(&lhs, &rhs),
-        };
-        Ok(result.into())
+        Ok(expr.op.eval(&lhs, &rhs, self.on_error)?.into())
+    }
+
+    fn eval_unary_op(&mut self, env: &mut Env, expr: &UnaryOp) -> Return {
+        let value = self.decode_value(env, &expr.value)?;
+
+        Ok(expr.op.eval(&value, self.on_error)?.into())
     }
 
     fn eval_while(&mut self, env: &mut Env, test: &BoolExpr, code: ExprSlice) -> Return {
@@ -94,7 +104,7 @@ impl Program {
                     continue;
                 }
             }
-            self.eval_expr(env, line);
+            self.eval_expr(env, line)?;
         }
 
         Ok(Expr::Pass)
@@ -118,7 +128,7 @@ impl Program {
                     }
                 }
 
-                self.eval_expr(env, line);
+                self.eval_expr(env, line)?;
             }
         }
         Ok(Expr::Pass)
@@ -149,15 +159,35 @@ impl Program {
         self.eval_expr(env, expr)
     }
 
+    pub fn eval_call_native(&mut self, env: &mut Env, fun: NativeFun, params: &FunCall) -> Return {
+        let fail = |msg: String| Failed::Runtime(Fail { msg });
+        let mut values = Vec::with_capacity(fun.params.len());
+
+        for name in fun.params {
+            let param = match params.params.get(*name) {
+                Some(x) => self.eval_expr(env, x)?,
+                None => return Err(fail(format!("Missing param {} of {}", name, fun.name))),
+            };
+            match get_value(&param) {
+                Some(x) => values.push(self.decode_value(env, x)?.as_ref().clone()),
+                None => return Err(fail(format!("The param {} of {} is not a value", name, fun.name))),
+            }
+        }
+        Ok((fun.fun)(&values)?.into())
+    }
+
     pub fn eval_call(&mut self, parent: &mut Env, expr: &FunCall) -> Return {
         let mut env = Env::child(parent.clone());
 
         match parent.find_fun(&expr.name) {
             Some(f) => match &f.body {
-                Some(code) => self.eval_call_simple(&mut env, &f, expr, code),
+                Some(code) => self.eval_call_simple(&mut env, f, expr, code),
                 None => unreachable!(),
             },
-            None => unimplemented!(),
+            None => match self.natives.get(&expr.name).copied() {
+                Some(f) => self.eval_call_native(&mut env, f, expr),
+                None => unimplemented!(),
+            },
         }
     }
 
@@ -186,6 +216,7 @@ impl Program {
             Expr::ForI(name, range, code) => self.eval_for_range(env, name, range, code),
             Expr::If(code, if_ok, if_false) => self.eval_if(env, code, if_ok, if_false),
             Expr::BinOp(code) => self.eval_bin_op(env, code),
+            Expr::UnaryOp(code) => self.eval_unary_op(env, code),
             Expr::CmpOp(code) => {
                 let result = self.eval_cmp(env, code)?;
                 Ok(result.into())
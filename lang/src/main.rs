@@ -1,6 +1,7 @@
-use tablam_core;
-
+//The interpreter is only run by the tests until main read a program
+#[allow(dead_code)]
 mod ast;
+#[allow(dead_code)]
 mod interpreter;
 
 #[cfg(test)]
@@ -1,9 +1,16 @@
 use tablam_core::types::DataType as DT;
 use tablam_core::types::CompareOp as CP;
+use tablam_core::types::OnError;
+use tablam_core::types as TT;
+use tablam_core::stdlib::time;
+use std::convert::TryFrom;
 use super::ast::*;
 
 fn _eval_expr(input:&Expr, output:&Expr) {
-    let mut program = Program::new();
+    _eval_expr_with(&mut Program::new(), input, output)
+}
+
+fn _eval_expr_with(program:&mut Program, input:&Expr, output:&Expr) {
     let mut env =Env::empty();
 
     match &program.eval_expr(&mut env, input) {
@@ -31,8 +38,8 @@ fn eval_cmp()
     let one:Value = 1.into();
     let two:Value = 2.into();
 
-    let cmp1 = eq( one.clone().into(), two.clone().into()).into();
-    let cmp2 = not_eq( one.into(), two.into()).into();
+    let cmp1 = eq( one.clone(), two.clone()).into();
+    let cmp2 = not_eq( one, two).into();
 
     _eval_expr(&cmp1, &false.into());
     _eval_expr(&cmp2, &true.into());
@@ -66,6 +73,80 @@ fn eval_bin_op()
     ])
 }
 
+#[test]
+fn eval_math_op()
+{
+    let seven:Value = 7i64.into();
+    let two:Value = 2i64.into();
+    let minus:Value = (-7i64).into();
+
+    _eval_exprs(&[
+        (mod_op(seven.clone(), two.clone()), 1i64.into()),
+        (int_div_op(seven.clone(), two.clone()), 3i64.into()),
+        (pow_op(seven.clone(), two.clone()), 49i64.into()),
+        (bin_op(TT::BinOp::Shl, seven.clone(), two.clone()), 28i64.into()),
+        (neg_op(seven), (-7i64).into()),
+        (abs_op(minus), 7i64.into()),
+    ])
+}
+
+#[test]
+fn eval_bin_op_fail()
+{
+    let mut program = Program::new();
+    let mut env =Env::empty();
+    let one:Value = 1i64.into();
+    let yes:Value = true.into();
+
+    match program.eval_expr(&mut env, &plus_op(one, yes)) {
+        Err(Failed::Runtime(fail)) => assert!(fail.msg.contains("mismatch")),
+        x => panic!("{:?}", x),
+    }
+}
+
+#[test]
+fn eval_fun_time()
+{
+    let at = time::time_parse_datetime(&"2019-03-07 23:59".into(), &"%Y-%m-%d %H:%M".into());
+    time::freeze(TT::TimeStamp::try_from(at.unwrap()).unwrap());
+
+    let part:Expr = "day".into();
+    let call1 = fun_call("today", &[]);
+    let call2 = fun_call("part", &[("of", call1.into()), ("part", part.into())]);
+    _eval_expr(&call2, &7i64.into());
+
+    let pattern:Expr = "%H:%M".into();
+    let call1 = fun_call("now", &[]);
+    let call2 = fun_call("format", &[("of", call1.into()), ("pattern", pattern.into())]);
+    _eval_expr(&call2, &"23:59".into());
+    time::unfreeze();
+}
+
+#[test]
+fn eval_bin_op_overflow()
+{
+    let mut program = Program::new();
+    let mut env =Env::empty();
+    let max:Value = i64::MAX.into();
+    let one:Value = 1i64.into();
+    let zero:Value = 0i64.into();
+
+    match program.eval_expr(&mut env, &div_op(one.clone(), zero.clone())) {
+        Err(Failed::Runtime(fail)) => assert!(fail.msg.contains("zero")),
+        x => panic!("{:?}", x),
+    }
+    match program.eval_expr(&mut env, &plus_op(max.clone(), one.clone())) {
+        Err(Failed::Runtime(fail)) => assert!(fail.msg.contains("Overflow")),
+        x => panic!("{:?}", x),
+    }
+
+    program.on_error = OnError::Saturate;
+    _eval_expr_with(&mut program, &plus_op(max.clone(), one.clone()), &i64::MAX.into());
+
+    program.on_error = OnError::Null;
+    _eval_expr_with(&mut program, &div_op(one, zero), &TT::Scalar::None.into());
+}
+
 #[test]
 fn eval_while()
 {
@@ -116,4 +197,27 @@ fn eval_fun()
     let full = block_lines(vec![fun1, call1]);
 
     _eval_expr(&full, &3.into())
-}
\ No newline at end of file
+}
+
+#[test]
+fn eval_fun_native()
+{
+    let name:Expr = " Años ".into();
+    let call1 = fun_call("upper", &[("text", name.into())]);
+    let call2 = fun_call("len", &[("text", call1.into())]);
+    _eval_expr(&call2, &6i64.into());
+
+    let name:Expr = "a,b".into();
+    let sep:Expr = ",".into();
+    let call1 = fun_call("split", &[("text", name.into()), ("sep", sep.clone().into())]);
+    let call2 = fun_call("join", &[("of", call1.into()), ("sep", sep.into())]);
+    _eval_expr(&call2, &"a,b".into());
+
+    let mut program = Program::new();
+    let mut env =Env::empty();
+    let call = fun_call("trim", &[]);
+    match program.eval_expr(&mut env, &call) {
+        Err(Failed::Runtime(fail)) => assert!(fail.msg.contains("Missing param text")),
+        x => panic!("{:?}", x),
+    }
+}